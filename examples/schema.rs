@@ -3,8 +3,15 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use guess::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, StatusResponse};
-use guess::state::State;
+use guess::msg::{
+    ActionSpecResponse, AllBalancesResponse, AuthorizationResponse, BalanceResponse, BetResponse,
+    BonusResponse, BuildActionResponse, CommitmentFormatResponse, ConfigResponse, ExecuteMsg,
+    FlowStatsResponse, FullStateResponse, InstantiateMsg, LiabilitiesResponse, LimitsResponse,
+    LotteryResult, PauseReasonResponse, QueryMsg, RealizedEdgeResponse, ReconcileResponse,
+    ResultResponse, RoundMetricsResponse, RoundsResponse, SolvencyResponse, StateDigestResponse,
+    StatusResponse, SuggestedBonusResponse, TransitionGraphResponse,
+};
+use guess::state::{DrawHistogram, RoundRecord, State, UserStats};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -17,4 +24,31 @@ fn main() {
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(State), &out_dir);
     export_schema(&schema_for!(StatusResponse), &out_dir);
+    export_schema(&schema_for!(ActionSpecResponse), &out_dir);
+    export_schema(&schema_for!(FlowStatsResponse), &out_dir);
+    export_schema(&schema_for!(AuthorizationResponse), &out_dir);
+    export_schema(&schema_for!(ReconcileResponse), &out_dir);
+    export_schema(&schema_for!(LiabilitiesResponse), &out_dir);
+    export_schema(&schema_for!(SuggestedBonusResponse), &out_dir);
+    export_schema(&schema_for!(StateDigestResponse), &out_dir);
+    export_schema(&schema_for!(AllBalancesResponse), &out_dir);
+    export_schema(&schema_for!(SolvencyResponse), &out_dir);
+    export_schema(&schema_for!(RealizedEdgeResponse), &out_dir);
+    export_schema(&schema_for!(FullStateResponse), &out_dir);
+    export_schema(&schema_for!(PauseReasonResponse), &out_dir);
+    export_schema(&schema_for!(RoundMetricsResponse), &out_dir);
+    export_schema(&schema_for!(CommitmentFormatResponse), &out_dir);
+    export_schema(&schema_for!(RoundsResponse), &out_dir);
+    export_schema(&schema_for!(BalanceResponse), &out_dir);
+    export_schema(&schema_for!(LimitsResponse), &out_dir);
+    export_schema(&schema_for!(BetResponse), &out_dir);
+    export_schema(&schema_for!(BuildActionResponse), &out_dir);
+    export_schema(&schema_for!(TransitionGraphResponse), &out_dir);
+    export_schema(&schema_for!(LotteryResult), &out_dir);
+    export_schema(&schema_for!(ResultResponse), &out_dir);
+    export_schema(&schema_for!(BonusResponse), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(RoundRecord), &out_dir);
+    export_schema(&schema_for!(DrawHistogram), &out_dir);
+    export_schema(&schema_for!(UserStats), &out_dir);
 }
@@ -0,0 +1,365 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Storage, Timestamp};
+
+use crate::error::ContractError;
+use crate::state::{GuessMode, LotteryCaller, Phase, State, BETS};
+
+/// 可以执行某个操作的调用者
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Caller {
+    /// 只有管理员
+    Owner,
+    /// 本轮的玩家，还没有玩家或者玩家还没有付清下注时任何人都可以成为玩家
+    Player,
+    /// 本轮的玩家或者管理员
+    PlayerOrOwner,
+    /// 本轮已下注的玩家，多人模式下为任一下注的玩家
+    Bettor,
+    /// 任何人
+    Anyone,
+    /// 管理员提名的新管理员
+    ProposedOwner,
+}
+
+impl From<LotteryCaller> for Caller {
+    fn from(caller: LotteryCaller) -> Self {
+        match caller {
+            LotteryCaller::Anyone => Caller::Anyone,
+            LotteryCaller::PlayerOrOwner => Caller::PlayerOrOwner,
+            LotteryCaller::OwnerOnly => Caller::Owner,
+        }
+    }
+}
+
+/// 执行操作前本轮还需要满足的条件
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Guard {
+    /// 管理员已提交本轮数字的承诺
+    Committed,
+    /// 管理员已揭示本轮的数字，或者揭示已超时
+    Revealed,
+    /// 管理员还没有揭示本轮的数字
+    Unrevealed,
+    /// 本轮还没有截止下注
+    BettingOpen,
+    /// 单人模式下玩家的下注已付清
+    BetComplete,
+    /// 本轮已经截止下注
+    BettingClosed,
+    /// 截止下注前，或者还没有已付清的下注
+    Cancellable,
+}
+
+impl Guard {
+    /// 当前状态是否满足条件
+    pub fn holds(self, state: &State, now: Timestamp) -> bool {
+        match self {
+            Guard::Committed => state.commit_hash.is_some(),
+            Guard::Revealed => state.revealed || state.reveal_expired(now),
+            Guard::Unrevealed => !state.revealed,
+            Guard::BettingOpen => !state.betting_closed(now),
+            Guard::BetComplete => state.multi_player || state.user.is_none() || state.bet_complete,
+            Guard::BettingClosed => state.betting_closed(now),
+            Guard::Cancellable => {
+                !state.betting_closed(now)
+                    || !state.has_player()
+                    || (!state.multi_player && !state.bet_complete)
+            }
+        }
+    }
+
+    /// 不满足条件时返回的错误
+    fn error(self) -> ContractError {
+        match self {
+            Guard::Committed => ContractError::NoCommitment {},
+            Guard::Revealed => ContractError::NotRevealed {},
+            Guard::Unrevealed => ContractError::AlreadyRevealed {},
+            Guard::BettingOpen => ContractError::BettingClosed {},
+            Guard::BetComplete => ContractError::BetIncomplete {},
+            Guard::BettingClosed => ContractError::BettingOpen {},
+            Guard::Cancellable => ContractError::BettingClosed {},
+        }
+    }
+}
+
+/// 合约的执行操作，和 `ExecuteMsg` 的变体一一对应
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Guess,
+    GuessFor,
+    GuessExact,
+    AddBonus,
+    Reset,
+    CancelRound,
+    Start,
+    Lottery,
+    Claim,
+    Resume,
+    TopUp,
+    WithdrawFees,
+    CollectDust,
+    WithdrawReserve,
+    Commit,
+    Reveal,
+    TransferOwnership,
+    AcceptOwnership,
+}
+
+impl Action {
+    /// 所有的执行操作
+    pub const ALL: &'static [Action] = &[
+        Action::Guess,
+        Action::GuessFor,
+        Action::GuessExact,
+        Action::AddBonus,
+        Action::Reset,
+        Action::CancelRound,
+        Action::Start,
+        Action::Lottery,
+        Action::Claim,
+        Action::Resume,
+        Action::TopUp,
+        Action::WithdrawFees,
+        Action::CollectDust,
+        Action::WithdrawReserve,
+        Action::Commit,
+        Action::Reveal,
+        Action::TransferOwnership,
+        Action::AcceptOwnership,
+    ];
+
+    /// 操作名称，和 `ExecuteMsg` 序列化后的名称一致
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Guess => "guess",
+            Action::GuessFor => "guess_for",
+            Action::GuessExact => "guess_exact",
+            Action::AddBonus => "add_bonus",
+            Action::Reset => "reset",
+            Action::CancelRound => "cancel_round",
+            Action::Start => "start",
+            Action::Lottery => "lottery",
+            Action::Claim => "claim",
+            Action::Resume => "resume",
+            Action::TopUp => "top_up",
+            Action::WithdrawFees => "withdraw_fees",
+            Action::CollectDust => "collect_dust",
+            Action::WithdrawReserve => "withdraw_reserve",
+            Action::Commit => "commit",
+            Action::Reveal => "reveal",
+            Action::TransferOwnership => "transfer_ownership",
+            Action::AcceptOwnership => "accept_ownership",
+        }
+    }
+
+    /// 根据名称查找操作
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+
+    /// 操作规则表
+    ///
+    /// 返回操作可以执行的阶段和调用者，执行函数通过 `authorize` 做检查，
+    /// `GetActionSpec` 查询也直接读取这张表，两者不会不一致。
+    /// 部分规则由合约配置、本轮的状态和当前时间决定，所以需要传入当前状态和时间
+    /// * 截止下注后任何人都可以开奖，管理员不在时玩家的资金也不会被卡住
+    /// * 还没有玩家下注时由管理员取消本轮，玩家下注后管理员已经知道本轮的数字，只有玩家可以取消
+    /// * 截止下注后单人模式下还没有付清的下注已经不能完成，任何人都可以取消
+    pub fn rule(self, state: &State, now: Timestamp) -> (&'static [Phase], Caller) {
+        match self {
+            Action::Guess | Action::GuessExact => (&[Phase::Betting], Caller::Player),
+            Action::GuessFor => (&[Phase::Betting], Caller::Anyone),
+            Action::AddBonus => (&[Phase::Setup], Caller::Owner),
+            Action::Reset => (
+                &[Phase::Setup, Phase::Betting, Phase::Resolved],
+                Caller::Owner,
+            ),
+            Action::CancelRound => {
+                // 已开奖的轮次由管理员重置，不再区分玩家
+                let caller = if !state.has_player() || state.phase() == Phase::Resolved {
+                    Caller::Owner
+                } else if !state.multi_player && !state.bet_complete && state.betting_closed(now) {
+                    Caller::Anyone
+                } else {
+                    Caller::Bettor
+                };
+                (&[Phase::Setup, Phase::Betting], caller)
+            }
+            Action::Start | Action::Commit => (&[Phase::Setup], Caller::Owner),
+            Action::Reveal => (&[Phase::Betting], Caller::Owner),
+            Action::AcceptOwnership => (
+                &[Phase::Setup, Phase::Betting, Phase::Resolved],
+                Caller::ProposedOwner,
+            ),
+            Action::Lottery if state.betting_closed(now) || state.reveal_expired(now) => {
+                (&[Phase::Betting], Caller::Anyone)
+            }
+            Action::Lottery => (&[Phase::Betting], state.lottery_caller.into()),
+            Action::Claim => (
+                &[Phase::Setup, Phase::Betting, Phase::Resolved],
+                Caller::Anyone,
+            ),
+            Action::Resume
+            | Action::TopUp
+            | Action::WithdrawFees
+            | Action::CollectDust
+            | Action::WithdrawReserve
+            | Action::TransferOwnership => (
+                &[Phase::Setup, Phase::Betting, Phase::Resolved],
+                Caller::Owner,
+            ),
+        }
+    }
+
+    /// 当前配置下是否可以执行该操作，和执行函数中对配置的检查一致
+    ///
+    /// 玩法决定使用哪种下注，配置了 `beacon_root` 时不需要提交和揭示承诺
+    pub fn enabled(self, state: &State) -> bool {
+        match self {
+            Action::Guess => state.guess_mode == GuessMode::Parity,
+            Action::GuessFor => state.guess_mode == GuessMode::Parity && state.allow_guess_for,
+            Action::GuessExact => state.guess_mode == GuessMode::Exact,
+            Action::Commit | Action::Reveal => state.beacon_root.is_none(),
+            _ => true,
+        }
+    }
+
+    /// 执行操作前本轮还需要满足的条件，由合约配置决定
+    ///
+    /// 没有配置 `beacon_root` 时，开放投注前需要提交承诺，开奖前需要揭示数字；
+    /// 揭示后不能再下注或者取消本轮，截止下注后不能再下注
+    ///
+    /// 使用信标的随机数时，截止下注后才能开奖，玩家也不能再取消已付清的下注
+    pub fn guards(self, state: &State) -> Vec<Guard> {
+        match self {
+            Action::Guess | Action::GuessFor | Action::GuessExact => {
+                vec![Guard::BettingOpen, Guard::Unrevealed]
+            }
+            Action::Start if state.beacon_root.is_none() => vec![Guard::Committed],
+            Action::Lottery if state.beacon_root.is_none() => {
+                vec![Guard::BetComplete, Guard::Revealed]
+            }
+            Action::Lottery => vec![Guard::BettingClosed, Guard::BetComplete],
+            Action::Reveal => vec![Guard::Unrevealed, Guard::BetComplete],
+            Action::CancelRound if state.beacon_root.is_none() => vec![Guard::Unrevealed],
+            Action::CancelRound => vec![Guard::Unrevealed, Guard::Cancellable],
+            _ => vec![],
+        }
+    }
+
+    /// 在 `from` 阶段执行操作后进入的阶段
+    pub fn transition(self, from: Phase) -> Phase {
+        match self {
+            Action::Start => Phase::Betting,
+            Action::Lottery => Phase::Resolved,
+            Action::Reset | Action::CancelRound => Phase::Setup,
+            _ => from,
+        }
+    }
+
+    /// 当前配置下不能使用该操作时返回的错误
+    fn disabled_error(self, state: &State) -> ContractError {
+        match self {
+            Action::GuessFor if !state.allow_guess_for => ContractError::GuessForDisabled {},
+            Action::Commit | Action::Reveal => ContractError::BeaconConfigured {},
+            _ => ContractError::WrongGuessMode {},
+        }
+    }
+
+    /// 在不允许的阶段执行操作时返回的错误
+    fn phase_error(self) -> ContractError {
+        match self {
+            Action::Guess
+            | Action::GuessFor
+            | Action::GuessExact
+            | Action::Lottery
+            | Action::Reveal => ContractError::NotReady {},
+            Action::CancelRound => ContractError::RoundSettled {},
+            Action::AddBonus
+            | Action::Reset
+            | Action::Start
+            | Action::Commit
+            | Action::Claim
+            | Action::Resume
+            | Action::TopUp
+            | Action::WithdrawFees
+            | Action::CollectDust
+            | Action::WithdrawReserve
+            | Action::TransferOwnership
+            | Action::AcceptOwnership => ContractError::IsPlaying {},
+        }
+    }
+}
+
+/// 判断地址是否是规则要求的调用者
+pub fn is_caller(storage: &dyn Storage, state: &State, caller: Caller, sender: &Addr) -> bool {
+    match caller {
+        Caller::Owner => &state.owner == sender,
+        Caller::Player => match state.user.as_ref() {
+            Some(user) => user == sender || !state.bet_complete,
+            None => true,
+        },
+        Caller::PlayerOrOwner => &state.owner == sender || state.user.as_ref() == Some(sender),
+        Caller::Bettor if state.multi_player => BETS.has(storage, sender),
+        Caller::Bettor => state.user.as_ref() == Some(sender),
+        Caller::Anyone => true,
+        Caller::ProposedOwner => state.proposed_owner.as_ref() == Some(sender),
+    }
+}
+
+/// 判断地址现在能否执行操作
+pub fn is_authorized(
+    storage: &dyn Storage,
+    state: &State,
+    action: Action,
+    sender: &Addr,
+    now: Timestamp,
+) -> bool {
+    authorize(storage, state, action, sender, now).is_ok()
+}
+
+/// 按规则表检查调用者、游戏阶段、当前配置和本轮的条件
+///
+/// 调用者不符合返回 `Unauthorized`，其余检查见 `check_ready`
+pub fn authorize(
+    storage: &dyn Storage,
+    state: &State,
+    action: Action,
+    sender: &Addr,
+    now: Timestamp,
+) -> Result<(), ContractError> {
+    let (_, caller) = action.rule(state, now);
+    if !is_caller(storage, state, caller, sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_ready(state, action, now)
+}
+
+/// 检查游戏阶段、当前配置和本轮的条件，不检查调用者
+///
+/// 阶段不符合返回该操作对应的错误，当前配置下不能使用返回对应的错误，条件不满足返回第一个不满足的条件对应的错误
+pub fn check_ready(state: &State, action: Action, now: Timestamp) -> Result<(), ContractError> {
+    let (phases, _) = action.rule(state, now);
+    if !phases.contains(&state.phase()) {
+        return Err(action.phase_error());
+    }
+    if !action.enabled(state) {
+        return Err(action.disabled_error(state));
+    }
+    match action
+        .guards(state)
+        .into_iter()
+        .find(|guard| !guard.holds(state, now))
+    {
+        Some(guard) => Err(guard.error()),
+        None => Ok(()),
+    }
+}
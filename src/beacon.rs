@@ -0,0 +1,39 @@
+use cosmwasm_std::Binary;
+use sha2::{Digest, Sha256};
+
+/// 某一轮随机数对应的叶子节点
+///
+/// 叶子包含轮次，同一棵树中其他轮次的随机数不能用于本轮
+pub fn leaf(round: u64, randomness: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain(round.to_be_bytes())
+        .chain(randomness)
+        .finalize()
+        .to_vec()
+}
+
+/// 校验默克尔证明
+///
+/// 每一层把当前节点和证明中的兄弟节点按字节序排序后拼接再求哈希，最后得到的节点必须等于 `root`
+pub fn verify_proof(root: &[u8], leaf: Vec<u8>, proof: &[Binary]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        let sibling = sibling.as_slice();
+        let (left, right) = if node.as_slice() <= sibling {
+            (node.as_slice(), sibling)
+        } else {
+            (sibling, node.as_slice())
+        };
+        Sha256::new().chain(left).chain(right).finalize().to_vec()
+    });
+    computed == root
+}
+
+/// 由随机数得出本轮的数字，结果在 `min_number` 和 `max_number` 之间
+///
+/// 取哈希的前两个字节对区间长度取模，区间最长 256，偏差可以忽略
+pub fn draw(randomness: &[u8], min_number: i8, max_number: i8) -> i8 {
+    let digest = Sha256::digest(randomness);
+    let span = (i16::from(max_number) - i16::from(min_number) + 1) as u16;
+    let offset = u16::from_be_bytes([digest[0], digest[1]]) % span;
+    (i16::from(min_number) + offset as i16) as i8
+}
@@ -1,10 +1,32 @@
-use cosmwasm_std::{Coin, MessageInfo, Uint128};
+use cosmwasm_std::{MessageInfo, Uint128};
 
-pub(crate) static DENOM: &str = "uluna";
+use crate::error::ContractError;
 
-pub fn get_coin_u128(info: &MessageInfo) -> Uint128 {
-    match info.funds.as_slice() {
-        [Coin { denom, amount }, ..] if denom == DENOM => *amount,
-        _ => Uint128::new(0),
+pub(crate) static DEFAULT_DENOM: &str = "uluna";
+
+/// 附带资金中指定币种的总金额，同一币种出现多次时累加，其他币种忽略
+pub fn get_coin_u128(info: &MessageInfo, expected: &str) -> Uint128 {
+    info.funds
+        .iter()
+        .filter(|coin| coin.denom == expected)
+        .map(|coin| coin.amount)
+        .sum()
+}
+
+/// 计算 `a - b`，以带符号的字符串表示
+pub fn signed_diff(a: Uint128, b: Uint128) -> String {
+    if a >= b {
+        (a - b).to_string()
+    } else {
+        format!("-{}", b - a)
+    }
+}
+
+/// 不接受转账的操作附带了资金时返回 `UnexpectedFunds`
+pub fn assert_no_funds(info: &MessageInfo) -> Result<(), ContractError> {
+    if info.funds.is_empty() {
+        Ok(())
+    } else {
+        Err(ContractError::UnexpectedFunds {})
     }
 }
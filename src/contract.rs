@@ -1,25 +1,122 @@
+use std::convert::TryInto;
+
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, BankMsg, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128,
+    coins, to_binary, to_vec, Addr, BankMsg, Binary, Coin, Decimal, Deps, DepsMut, Env, Event,
+    MessageInfo, Order, Response, StdError, StdResult, Storage, Timestamp, Uint128,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::{Bound, U64Key};
+use sha2::{Digest, Sha256};
 
-use crate::coin_helper::{get_coin_u128, DENOM};
+use crate::action::{authorize, check_ready, is_authorized, Action};
+use crate::beacon;
+use crate::coin_helper::{assert_no_funds, get_coin_u128, signed_diff, DEFAULT_DENOM};
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, StatusResponse};
-use crate::state::{State, STATE};
+use crate::msg::{
+    ActionSpec, ActionSpecResponse, AllBalancesResponse, AuthorizationResponse, BalanceResponse,
+    BetResponse, BonusResponse, BuildActionResponse, CommitmentFormatResponse, ConfigResponse,
+    ExecuteMsg, FlowStatsResponse, FullStateResponse, InstantiateMsg, LiabilitiesResponse,
+    LimitsResponse, LotteryResult, MigrateMsg, PauseReasonResponse, QueryMsg, RealizedEdgeResponse,
+    ReconcileResponse, ResultResponse, RoundMetricsResponse, RoundsResponse, SolvencyResponse,
+    StateDigestResponse, StatusResponse, SuggestedBonusResponse, Transition,
+    TransitionGraphResponse,
+};
+use crate::state::{
+    Bet, Claim, DailyLoss, DrawHistogram, FlowStats, GuessMode, Phase, RoundRecord, State,
+    UserStats, BETS, CLAIMS, DAILY_LOSSES, DRAW_HISTOGRAM, FLOW_STATS, ROUNDS, STATE, STATE_V0_1,
+    USER_STATS,
+};
 
 // 合约版本信息，管理员可以用来对合约进行升级维护
 const CONTRACT_NAME: &str = "crates.io:guess";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// 每轮备注信息的默认最大长度
+const DEFAULT_MAX_METADATA_LEN: u32 = 128;
+// 截止下注后等待管理员揭示数字的默认秒数
+const DEFAULT_REVEAL_TIMEOUT_SECONDS: u64 = 24 * 60 * 60;
+
+// 本轮数字的默认范围
+const DEFAULT_MIN_NUMBER: i8 = 0;
+const DEFAULT_MAX_NUMBER: i8 = 100;
+
+// 按天统计输掉的金额
+const SECONDS_PER_DAY: u64 = 86_400;
+
+// 分页查询的默认和最大条数
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+// 升级步骤，每次修改存储格式时在末尾添加一步，把上一个版本的存储改写为该版本的格式
+type MigrationStep = fn(&mut dyn Storage) -> StdResult<()>;
+const MIGRATIONS: &[(&str, MigrationStep)] = &[("0.2.0", migrate_to_0_2)];
+
 /// 升级合约
 ///
-/// 对合约进行升级
+/// 对合约进行升级，依次执行存储的版本之后的所有升级步骤
+/// * 存储的合约名称不一致返回 `WrongContract`
+/// * 存储的版本比当前版本新返回 `CannotDowngrade`
+/// * 返回属性 `action`、`from_version`、`to_version`
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
-    Ok(Response::default())
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::WrongContract {
+            name: stored.contract,
+        });
+    }
+    let from = parse_version(&stored.version)?;
+    if from > parse_version(CONTRACT_VERSION)? {
+        return Err(ContractError::CannotDowngrade {
+            version: stored.version,
+        });
+    }
+    for (version, step) in MIGRATIONS {
+        if from < parse_version(version)? {
+            step(deps.storage)?;
+        }
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
+/// 把 `major.minor.patch` 形式的版本号解析为可以比较大小的数字
+fn parse_version(version: &str) -> Result<Vec<u64>, ContractError> {
+    version
+        .split('.')
+        .map(|part| {
+            part.parse().map_err(|_| ContractError::InvalidVersion {
+                version: version.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// 从 0.1 版本升级
+///
+/// 旧的状态只有一轮游戏的基本信息，新增的配置使用初始化时的默认值
+/// 旧版本由管理员重置时直接设置数字，没有承诺，进行中的一轮视为已揭示
+fn migrate_to_0_2(storage: &mut dyn Storage) -> StdResult<()> {
+    let old = STATE_V0_1.load(storage)?;
+    let mut state = new_state(old.owner, InstantiateMsg::default());
+    state.guess_number = old.guess_number;
+    state.is_playing = old.is_playing;
+    state.is_lottery = old.is_lottery;
+    state.guess_is_odd = old.guess_is_odd;
+    state.user = old.user;
+    state.bonus = old.bonus;
+    state.user_payed = old.user_payed;
+    state.revealed = old.is_playing || old.is_lottery;
+    state.bet_complete = state.user.is_some();
+    STATE.save(storage, &state)?;
+    FLOW_STATS.save(storage, &FlowStats::default())?;
+    DRAW_HISTOGRAM.save(storage, &DrawHistogram::default())
 }
 
 /// 初始化
@@ -36,24 +133,133 @@ pub fn instantiate(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    _msg: InstantiateMsg,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
-    let state = State {
+    if msg.bonus_growth_bps_per_round > 10_000 {
+        return Err(ContractError::InvalidConfig {
+            field: "bonus_growth_bps_per_round".to_string(),
+        });
+    }
+    if msg.commission_bps > 10_000 {
+        return Err(ContractError::InvalidConfig {
+            field: "commission_bps".to_string(),
+        });
+    }
+    if msg.multi_player && msg.guess_mode == GuessMode::Exact {
+        return Err(ContractError::InvalidConfig {
+            field: "guess_mode".to_string(),
+        });
+    }
+    if matches!(msg.max_bonus, Some(max_bonus) if max_bonus < msg.min_bonus) {
+        return Err(ContractError::InvalidConfig {
+            field: "max_bonus".to_string(),
+        });
+    }
+    // 信标的随机数公开后玩家就能知道结果，必须在公开前截止下注
+    if msg.beacon_root.is_some() && msg.bet_duration_seconds.is_none() {
+        return Err(ContractError::InvalidConfig {
+            field: "bet_duration_seconds".to_string(),
+        });
+    }
+    // 多人模式的结算不支持把奖金池留作下一轮
+    if msg.multi_player && msg.rollover_min_edge_bps.is_some() {
+        return Err(ContractError::InvalidConfig {
+            field: "rollover_min_edge_bps".to_string(),
+        });
+    }
+    // 下注金额不超过奖金，奖金池最多是本金的两倍
+    if msg.win_multiplier_bps != 0
+        && (msg.multi_player || !(10_000..=20_000).contains(&msg.win_multiplier_bps))
+    {
+        return Err(ContractError::InvalidConfig {
+            field: "win_multiplier_bps".to_string(),
+        });
+    }
+    let min_number = msg.min_number.unwrap_or(DEFAULT_MIN_NUMBER);
+    let max_number = msg.max_number.unwrap_or(DEFAULT_MAX_NUMBER);
+    if min_number > max_number {
+        return Err(ContractError::InvalidConfig {
+            field: "min_number".to_string(),
+        });
+    }
+    let state = new_state(info.sender.clone(), msg);
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    STATE.save(deps.storage, &state)?;
+    FLOW_STATS.save(deps.storage, &FlowStats::default())?;
+    DRAW_HISTOGRAM.save(deps.storage, &DrawHistogram::default())?;
+
+    Ok(Response::new()
+        .add_attribute("method", "instantiate")
+        .add_attribute("owner", info.sender))
+}
+
+/// 按初始化信息生成新的合约状态
+fn new_state(owner: Addr, msg: InstantiateMsg) -> State {
+    State {
         guess_number: 0,
-        owner: info.sender.clone(),
+        owner,
         guess_is_odd: false,
         bonus: Uint128::new(0),
         user_payed: Uint128::new(0),
         user: None,
         is_playing: false,
         is_lottery: false,
-    };
-    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    STATE.save(deps.storage, &state)?;
-
-    Ok(Response::new()
-        .add_attribute("method", "instantiate")
-        .add_attribute("owner", info.sender))
+        single_round_only: msg.single_round_only,
+        payout_delay_seconds: msg.payout_delay_seconds,
+        escrowed: Uint128::new(0),
+        sweep_stray_into_bonus: msg.sweep_stray_into_bonus,
+        min_payout_amount: msg.min_payout_amount,
+        match_tolerance: msg.match_tolerance,
+        bonus_growth_bps_per_round: msg.bonus_growth_bps_per_round,
+        base_bonus: Uint128::new(0),
+        rounds_started: 0,
+        suggested_bonus: Uint128::new(0),
+        lottery_caller: msg.lottery_caller,
+        max_metadata_len: msg.max_metadata_len.unwrap_or(DEFAULT_MAX_METADATA_LEN),
+        metadata: None,
+        min_seconds_between_bonus: msg.min_seconds_between_bonus,
+        last_bonus_time: None,
+        denom: msg.denom.unwrap_or_else(|| DEFAULT_DENOM.to_string()),
+        pause_on_insolvency: msg.pause_on_insolvency,
+        paused: false,
+        pause_reason: None,
+        commission_bps: msg.commission_bps,
+        daily_loss_limit: msg.daily_loss_limit,
+        commit_hash: None,
+        revealed: false,
+        beacon_root: msg.beacon_root,
+        proposed_owner: None,
+        round_started_at: None,
+        bets_placed: 0,
+        multi_player: msg.multi_player,
+        free_rounds: msg.free_rounds,
+        round_count: 0,
+        min_number: msg.min_number.unwrap_or(DEFAULT_MIN_NUMBER),
+        max_number: msg.max_number.unwrap_or(DEFAULT_MAX_NUMBER),
+        block_reset_until_claimed: msg.block_reset_until_claimed,
+        allow_guess_for: msg.allow_guess_for,
+        guess_mode: msg.guess_mode,
+        guessed_number: 0,
+        min_bonus: msg.min_bonus,
+        max_bonus: msg.max_bonus,
+        rollover_min_edge_bps: msg.rollover_min_edge_bps,
+        rollover_bonus: Uint128::new(0),
+        bet_duration_seconds: msg.bet_duration_seconds,
+        bet_deadline: None,
+        min_distinct_winners: msg.min_distinct_winners,
+        accrued_fees: Uint128::new(0),
+        win_multiplier_bps: msg.win_multiplier_bps,
+        min_blocks: msg.min_blocks,
+        start_height: None,
+        bet_complete: false,
+        max_players: msg.max_players,
+        reveal_timeout_seconds: msg
+            .reveal_timeout_seconds
+            .unwrap_or(DEFAULT_REVEAL_TIMEOUT_SECONDS),
+        forfeited: false,
+        reserve: Uint128::new(0),
+        player_count: 0,
+    }
 }
 
 /// 执行操作
@@ -73,11 +279,31 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::Start {} => try_start(deps, info),
-        ExecuteMsg::Reset { num } => try_reset(deps, info, num, env),
-        ExecuteMsg::Guess { is_odd } => try_guess(deps, info, is_odd),
-        ExecuteMsg::AddBonus {} => try_add_bonus(deps, info),
-        ExecuteMsg::Lottery {} => try_lottery(deps, env),
+        ExecuteMsg::Start { metadata } => try_start(deps, info, env, metadata),
+        ExecuteMsg::Reset {} => try_reset(deps, info, env),
+        ExecuteMsg::CancelRound {} => try_cancel_round(deps, info, env),
+        ExecuteMsg::Commit { hash } => try_commit(deps, info, env, hash),
+        ExecuteMsg::Reveal { secret, nonce } => try_reveal(deps, info, env, secret, nonce),
+        ExecuteMsg::TransferOwnership { new_owner } => {
+            try_transfer_ownership(deps, info, env, new_owner)
+        }
+        ExecuteMsg::AcceptOwnership {} => try_accept_ownership(deps, info, env),
+        ExecuteMsg::Guess { is_odd } => try_guess(deps, info, env, is_odd),
+        ExecuteMsg::GuessFor {
+            beneficiary,
+            is_odd,
+        } => try_guess_for(deps, info, env, beneficiary, is_odd),
+        ExecuteMsg::GuessExact { number } => try_guess_exact(deps, info, env, number),
+        ExecuteMsg::AddBonus {} => try_add_bonus(deps, info, env),
+        ExecuteMsg::Lottery { randomness, proof } => {
+            try_lottery(deps, info, env, randomness, proof)
+        }
+        ExecuteMsg::Claim {} => try_claim(deps, info, env),
+        ExecuteMsg::Resume {} => try_resume(deps, info, env),
+        ExecuteMsg::WithdrawFees {} => try_withdraw_fees(deps, info, env),
+        ExecuteMsg::CollectDust {} => try_collect_dust(deps, info, env),
+        ExecuteMsg::WithdrawReserve {} => try_withdraw_reserve(deps, info, env),
+        ExecuteMsg::TopUp {} => try_top_up(deps, info, env),
     }
 }
 
@@ -86,248 +312,5443 @@ pub fn execute(
 /// * 必须先开放投注，用户才可以下注猜大小
 /// * 只能由合约管理员进行该操作
 /// * 开放后不能重复开放，只能重置合约状态才能重新开放
-pub fn try_start(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
-    // let balance = deps.querier.query_balance(env.contract.address, DENOM)?;
-    STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
-        if state.owner != info.sender {
-            return Err(ContractError::Unauthorized {});
-        }
-        if state.user.is_some() || state.is_playing {
-            return Err(ContractError::IsPlaying {});
+/// * 奖金为0时返回 `NoBonus`，低于 `min_bonus` 时返回 `BonusTooLow`
+/// * 没有配置 `beacon_root` 时，管理员没有提交本轮数字的承诺不能开放
+/// * 开启了 `sweep_stray_into_bonus` 时，合约中未记账的余额会并入奖金
+/// * 可以附带本轮的备注信息，长度不能超过 `max_metadata_len`
+/// * 以第一轮的奖金为基数，每开放一轮按 `bonus_growth_bps_per_round` 提高建议奖金
+/// * 开启了 `pause_on_insolvency` 时，余额不足以覆盖奖金会暂停押注
+/// * 配置了 `bet_duration_seconds` 时，开放后经过该秒数截止下注
+/// * 附带资金返回 `UnexpectedFunds`
+/// * 返回属性 `action`、`bonus`、`round`
+pub fn try_start(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    metadata: Option<String>,
+) -> Result<Response, ContractError> {
+    assert_no_funds(&info)?;
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::Start,
+        &info.sender,
+        env.block.time,
+    )?;
+    if let Some(metadata) = metadata.as_ref() {
+        if metadata.len() > state.max_metadata_len as usize {
+            return Err(ContractError::MetadataTooLong {
+                max: state.max_metadata_len,
+            });
         }
-        state.is_playing = true;
-        Ok(state)
-    })?;
+    }
+    state.metadata = metadata;
+    if state.sweep_stray_into_bonus {
+        let balance = deps
+            .querier
+            .query_balance(&env.contract.address, &state.denom)?;
+        let stray = balance.amount.saturating_sub(state.locked_funds());
+        state.bonus += stray;
+        FLOW_STATS.update(deps.storage, |mut stats| -> StdResult<_> {
+            stats.total_bonus_ever += stray;
+            Ok(stats)
+        })?;
+    }
+    // 没有奖金的一轮没有意义
+    if state.bonus.is_zero() {
+        return Err(ContractError::NoBonus {});
+    }
+    if state.bonus < state.min_bonus {
+        return Err(ContractError::BonusTooLow {});
+    }
+    if state.rounds_started == 0 {
+        state.base_bonus = state.bonus;
+        state.suggested_bonus = state.bonus;
+    }
+    state.rounds_started += 1;
+    state.suggested_bonus = state.suggested_bonus.multiply_ratio(
+        10_000 + u128::from(state.bonus_growth_bps_per_round),
+        10_000u128,
+    );
+    state.is_playing = true;
+    state.round_started_at = Some(env.block.time);
+    state.start_height = Some(env.block.height);
+    state.bet_deadline = state
+        .bet_duration_seconds
+        .map(|duration| env.block.time.plus_seconds(duration));
+    state.bets_placed = 0;
+    let mut res = Response::new()
+        .add_attribute("action", "start")
+        .add_attribute("bonus", state.bonus)
+        .add_attribute("round", state.rounds_started.to_string());
+    if let Some(shortfall) = insolvency_shortfall(deps.as_ref(), &env, &state)? {
+        res = res.add_event(pause_for_insolvency(&mut state, shortfall));
+    }
+    STATE.save(deps.storage, &state)?;
 
-    Ok(Response::new())
+    Ok(res)
 }
 
 /// 用户下注
 ///
 /// 用户下注并记录押的单还是双
-/// * 下注的金额需要和奖金额一致
+/// * 下注的金额累计达到奖金额才算下注完成，少付在 `match_tolerance` 内也视为足额，多付的部分退还
+/// * 单人模式下可以分多次付清，未付清时另返回属性 `remaining`
+/// * 单人模式下玩家还没有付清时其他人可以足额下注替换该玩家，原玩家已付的金额退还，否则返回 `Unauthorized`
 /// * 押大小后可以在开奖前修改单双
+/// * 多人模式下每个玩家各自下注，下注金额都需要和奖金额一致
+/// * 多人模式下玩家人数达到 `max_players` 时新的玩家不能下注，返回 `RoundFull`
+/// * 当天输掉的金额达到 `daily_loss_limit` 时返回 `LossLimitReached`
+/// * 管理员揭示数字后不能再下注
+/// * 超过本轮截止下注的时间返回 `BettingClosed`
+/// * 返回属性 `action`、`user`、`guess_is_odd`、`user_payed`（该玩家的下注金额），暂停押注时只返回 `action`
 pub fn try_guess(
     deps: DepsMut,
     info: MessageInfo,
+    env: Env,
     is_odd: bool,
 ) -> Result<Response, ContractError> {
-    STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
-        let pay = get_coin_u128(&info);
-        match state.user.as_ref() {
-            Some(user) => {
-                if user != &info.sender {
-                    return Err(ContractError::Unauthorized {});
-                }
-            }
-            None => state.user = Some(info.sender),
+    let state = STATE.load(deps.storage)?;
+    // 判断是否可押注和是否已开奖
+    authorize(
+        deps.storage,
+        &state,
+        Action::Guess,
+        &info.sender,
+        env.block.time,
+    )?;
+    let player = info.sender.clone();
+    place_bet(deps, info, env, state, player, is_odd, "guess")
+}
+
+/// 代他人下注
+///
+/// 调用者支付下注金额，`beneficiary` 作为本轮的玩家领取奖金和累计战绩
+/// * 开启了 `allow_guess_for` 才能代下注，否则返回 `GuessForDisabled`
+/// * 只能为本轮还没有下注的玩家代下注，之后修改单双由玩家自己完成，否则返回 `Unauthorized`
+/// * 本次多付的部分和暂停押注时的退款都退还给调用者
+/// * 已经记入的下注属于 `beneficiary`，被足额下注替换或者取消本轮时退还给 `beneficiary`，不退还给调用者
+/// * 其余规则和 `Guess` 相同，返回属性 `action`、`user`、`guess_is_odd`、`user_payed`
+pub fn try_guess_for(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    beneficiary: String,
+    is_odd: bool,
+) -> Result<Response, ContractError> {
+    let state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::GuessFor,
+        &info.sender,
+        env.block.time,
+    )?;
+    let beneficiary = deps.api.addr_validate(&beneficiary)?;
+    let has_bet = if state.multi_player {
+        BETS.has(deps.storage, &beneficiary)
+    } else {
+        state.user.is_some()
+    };
+    if has_bet {
+        return Err(ContractError::Unauthorized {});
+    }
+    place_bet(deps, info, env, state, beneficiary, is_odd, "guess_for")
+}
+
+/// 猜数字
+///
+/// 猜数字玩法下用户下注并记录猜的数字，开奖时猜中本轮的数字才获胜
+/// * 猜单双玩法下返回 `WrongGuessMode`
+/// * 数字不在 `min_number` 和 `max_number` 之间返回 `InvalidNumber`
+/// * 其余规则和 `Guess` 相同，返回属性 `action`、`user`、`guess_is_odd`、`user_payed`
+pub fn try_guess_exact(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    number: i8,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::GuessExact,
+        &info.sender,
+        env.block.time,
+    )?;
+    if number < state.min_number || number > state.max_number {
+        return Err(ContractError::InvalidNumber { num: number });
+    }
+    state.guessed_number = number;
+    let player = info.sender.clone();
+    place_bet(
+        deps,
+        info,
+        env,
+        state,
+        player,
+        number % 2 != 0,
+        "guess_exact",
+    )
+}
+
+/// 记录 `player` 的下注，资金由调用者支付，本次的退款也退还给调用者，被替换的下注退还给原玩家
+fn place_bet(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    mut state: State,
+    player: Addr,
+    is_odd: bool,
+    action: &str,
+) -> Result<Response, ContractError> {
+    let pay = get_coin_u128(&info, &state.denom);
+    let mut displaced = None;
+    let mut new_player = false;
+    if state.paused {
+        return Err(ContractError::Paused {});
+    }
+    if let Some(limit) = state.daily_loss_limit {
+        if daily_loss(deps.as_ref(), &player, &env)? >= limit {
+            return Err(ContractError::LossLimitReached {});
         }
-        // 判断是否可押注和是否已开奖
-        if !state.is_playing || state.is_lottery {
-            return Err(ContractError::NotReady {});
+    }
+    let previous = if state.multi_player {
+        let bet = BETS.may_load(deps.storage, &player)?;
+        // 人数已满时只有已下注的玩家可以修改下注
+        if let Some(max_players) = state.max_players {
+            if bet.is_none() && state.player_count >= max_players {
+                return Err(ContractError::RoundFull {});
+            }
         }
-        // 判断下注金额是否和奖金一致
-        if state.user_payed + pay != state.bonus {
-            return Err(ContractError::Pay {});
+        new_player = bet.is_none();
+        bet.map(|bet| bet.stake).unwrap_or_default()
+    } else {
+        // 未付清的下注可以被足额下注替换，防止少量付款占住本轮
+        if let Some(user) = state.user.clone().filter(|user| user != &player) {
+            if state.bet_complete || pay + state.match_tolerance < state.bonus {
+                return Err(ContractError::Unauthorized {});
+            }
+            displaced = Some((user, state.user_payed));
+            state.user_payed = Uint128::new(0);
         }
-        state.user_payed += pay;
+        state.user = Some(player.clone());
         state.guess_is_odd = is_odd;
-        Ok(state)
+        state.user_payed
+    };
+    // 判断下注金额是否足额，多付的部分退还
+    // 单人模式下可以分多次付清，多人模式下每次下注都需要足额
+    let total = previous + pay;
+    let complete = total + state.match_tolerance >= state.bonus;
+    if !complete && (state.multi_player || pay.is_zero()) {
+        return Err(ContractError::Pay {});
+    }
+    let refund = total.saturating_sub(state.bonus);
+    let stake = total - refund;
+    state.user_payed = state.user_payed - previous + stake;
+    // 余额不足时不接受本次下注，暂停押注并退还全部金额
+    if let Some(shortfall) = insolvency_shortfall(deps.as_ref(), &env, &state)? {
+        let mut state = STATE.load(deps.storage)?;
+        let event = pause_for_insolvency(&mut state, shortfall);
+        STATE.save(deps.storage, &state)?;
+        let mut res = Response::new()
+            .add_attribute("action", action)
+            .add_event(event);
+        if !pay.is_zero() {
+            res = res.add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: coins(pay.u128(), &state.denom),
+            });
+        }
+        return Ok(res);
+    }
+    state.bets_placed += 1;
+    if !state.multi_player {
+        state.bet_complete = complete;
+    }
+    if new_player {
+        state.player_count += 1;
+    }
+    STATE.save(deps.storage, &state)?;
+    if state.multi_player {
+        BETS.save(deps.storage, &player, &Bet { is_odd, stake })?;
+    }
+    let displaced_refund = displaced
+        .as_ref()
+        .map(|(_, amount)| *amount)
+        .unwrap_or_default();
+    FLOW_STATS.update(deps.storage, |mut stats| -> StdResult<_> {
+        stats.total_staked_ever += pay - refund;
+        stats.total_paid_ever += displaced_refund;
+        Ok(stats)
     })?;
 
-    Ok(Response::new())
+    let mut res = Response::new()
+        .add_attribute("action", action)
+        .add_attribute("user", &player)
+        .add_attribute("guess_is_odd", is_odd.to_string())
+        .add_attribute("user_payed", stake);
+    if !complete {
+        res = res.add_attribute("remaining", state.bonus - stake);
+    }
+    if !refund.is_zero() {
+        res = res.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: coins(refund.u128(), &state.denom),
+        });
+    }
+    if let Some((user, amount)) = displaced.filter(|(_, amount)| !amount.is_zero()) {
+        res = res
+            .add_attribute("displaced", &user)
+            .add_message(BankMsg::Send {
+                to_address: user.to_string(),
+                amount: coins(amount.u128(), &state.denom),
+            });
+    }
+    Ok(res)
+}
+
+/// 地址当天输掉的金额
+fn daily_loss(deps: Deps, address: &Addr, env: &Env) -> StdResult<Uint128> {
+    let today = env.block.time.seconds() / SECONDS_PER_DAY;
+    Ok(match DAILY_LOSSES.may_load(deps.storage, address)? {
+        Some(loss) if loss.day == today => loss.amount,
+        _ => Uint128::new(0),
+    })
+}
+
+/// 记录地址输掉的金额，跨天后重新累计
+fn record_loss(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    amount: Uint128,
+    env: &Env,
+) -> StdResult<()> {
+    let today = env.block.time.seconds() / SECONDS_PER_DAY;
+    DAILY_LOSSES.update(storage, address, |loss| -> StdResult<_> {
+        Ok(match loss {
+            Some(loss) if loss.day == today => DailyLoss {
+                day: today,
+                amount: loss.amount + amount,
+            },
+            _ => DailyLoss { day: today, amount },
+        })
+    })?;
+    Ok(())
 }
 
 /// 添加奖金
 ///
 /// 管理员在开放押注前添加奖金，如果游戏已开始不能进行操作
-pub fn try_add_bonus(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
-    STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
-        if state.owner != info.sender {
-            return Err(ContractError::Unauthorized {});
+/// * 距离上次添加不足 `min_seconds_between_bonus` 秒时返回 `TooFast`
+/// * 添加后奖金超过 `max_bonus` 时返回 `BonusTooHigh`
+/// * 返回属性 `action`、`amount`、`bonus`，`data` 为 `BonusResponse`
+pub fn try_add_bonus(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::AddBonus,
+        &info.sender,
+        env.block.time,
+    )?;
+    if let Some(last_bonus_time) = state.last_bonus_time {
+        if env.block.time < last_bonus_time.plus_seconds(state.min_seconds_between_bonus) {
+            return Err(ContractError::TooFast {});
         }
-        if state.user.is_some() || state.is_playing {
-            return Err(ContractError::IsPlaying {});
+    }
+    let pay = get_coin_u128(&info, &state.denom);
+    state.last_bonus_time = Some(env.block.time);
+    state.bonus += pay;
+    if let Some(max) = state.max_bonus {
+        if state.bonus > max {
+            return Err(ContractError::BonusTooHigh { max });
         }
-        let pay = get_coin_u128(&info);
-        state.bonus += pay;
-        Ok(state)
+    }
+    STATE.save(deps.storage, &state)?;
+    FLOW_STATS.update(deps.storage, |mut stats| -> StdResult<_> {
+        stats.total_bonus_ever += pay;
+        Ok(stats)
     })?;
 
-    Ok(Response::new())
+    Ok(Response::new()
+        .add_attribute("action", "add_bonus")
+        .add_attribute("amount", pay)
+        .add_attribute("bonus", state.bonus)
+        .set_data(to_binary(&BonusResponse {
+            total: state.bonus,
+            added: pay,
+        })?))
+}
+
+/// 提交承诺
+///
+/// 管理员在开放投注前提交 `sha256(secret || nonce)` 的十六进制编码，
+/// 开奖前再揭示 `secret` 和 `nonce`，下注时管理员无法再修改本轮的数字
+/// * 开放投注前可以重新提交
+/// * 配置了 `beacon_root` 时数字由信标得出，返回 `BeaconConfigured`
+pub fn try_commit(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    hash: String,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::Commit,
+        &info.sender,
+        env.block.time,
+    )?;
+    state.commit_hash = Some(hash.to_lowercase());
+    STATE.save(deps.storage, &state)?;
+    Ok(Response::new().add_attribute("action", "commit"))
+}
+
+/// 揭示数字
+///
+/// 用户下注后，管理员揭示 `secret` 和 `nonce`，校验与承诺一致后把 `secret` 作为本轮的数字
+/// * 与承诺不一致返回 `HashMismatch`
+/// * `secret` 不是 `i8` 范围内的整数，或者不是规范的十进制写法（如 `+5`、`05`、`-0`）返回 `InvalidSecret`
+/// * 数字不在 `min_number` 和 `max_number` 之间返回 `InvalidNumber`
+/// * 配置了 `beacon_root` 时数字由信标得出，返回 `BeaconConfigured`
+/// * 单人模式下玩家还没有付清下注返回 `BetIncomplete`，揭示后玩家就不能再补足或者取消
+/// * 返回属性 `action`、`guess_number`
+pub fn try_reveal(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    secret: String,
+    nonce: String,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::Reveal,
+        &info.sender,
+        env.block.time,
+    )?;
+    if !state.has_player() {
+        return Err(ContractError::NoPlayer {});
+    }
+    if state.commit_hash.as_deref() != Some(commitment_hash(&secret, &nonce).as_str()) {
+        return Err(ContractError::HashMismatch {});
+    }
+    // 只接受规范的写法，同一个承诺不能揭示为不同的 `secret`
+    let num: i8 = secret
+        .parse()
+        .map_err(|_| ContractError::InvalidSecret {})?;
+    if num.to_string() != secret {
+        return Err(ContractError::InvalidSecret {});
+    }
+    if num < state.min_number || num > state.max_number {
+        return Err(ContractError::InvalidNumber { num });
+    }
+    state.guess_number = num;
+    state.revealed = true;
+    STATE.save(deps.storage, &state)?;
+    Ok(Response::new()
+        .add_attribute("action", "reveal")
+        .add_attribute("guess_number", state.guess_number.to_string()))
+}
+
+/// 计算承诺
+///
+/// 把 `secret` 和 `nonce` 的 UTF-8 字节用 `:` 拼接，求 SHA-256 后以小写十六进制编码
+///
+/// 规范写法的 `secret` 不含 `:`，第一个 `:` 之前总是 `secret`，`nonce` 可以包含 `:`
+pub fn commitment_hash(secret: &str, nonce: &str) -> String {
+    hex::encode(Sha256::digest(format!("{}:{}", secret, nonce).as_bytes()))
 }
 
 /// 开奖
 ///
-/// 管理员和用户都可以进行开奖操作
+/// 按 `lottery_caller` 的配置，由任何人、玩家和管理员或者只由管理员开奖，截止下注后任何人都可以开奖
+/// 管理员揭示本轮的数字后才能开奖，配置了 `beacon_root` 时改用信标的随机数，截止下注前返回 `BettingOpen`
 /// 用户猜对，合约的奖金全部打到用户钱包
 /// 用户猜错，合约的奖金全部打到管理员钱包
-pub fn try_lottery(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
-    let state = STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
-        if state.is_lottery || state.user.is_none() {
-            return Err(ContractError::NotReady {});
-        }
-        state.is_lottery = true;
-        state.is_playing = false;
-        Ok(state)
-    })?;
+/// * 猜数字玩法下猜中本轮的数字才算猜对
+/// * 设置了发放延迟时，用户赢得的奖金先托管在合约中，期满后由用户领取
+/// * 配置了 `win_multiplier_bps` 时，用户猜对只获得本金的相应倍数，奖金池剩下的部分转给管理员，另返回属性 `house_share`
+/// * 奖金低于 `min_payout_amount` 时不转账，记入赢家的待领取余额
+/// * 用户获胜时按 `commission_bps` 从奖金中抽取佣金，累计在合约中由管理员通过 `WithdrawFees` 提取，用户的前 `free_rounds` 轮免收
+/// * 信标的随机数需要附带默克尔证明，证明无效返回 `BadProof`
+/// * 附带资金返回 `UnexpectedFunds`
+/// * 截止下注后超过 `reveal_timeout_seconds` 管理员仍没有揭示数字时，任何人都可以开奖，所有玩家按猜对结算；没有截止时间时从开放投注时开始计算
+/// * 单人模式下玩家还没有付清下注返回 `BetIncomplete`
+/// * 开放投注后不足 `min_blocks` 个区块返回 `TooSoon`
+/// * 单人模式下配置了 `rollover_min_edge_bps` 时，庄家获胜且庄家优势不低于该下限，奖金池不转给管理员，重置后作为下一轮的奖金
+/// * 返回属性 `action`、`winner`、`payout`、`commission`，奖金池留作下一轮时另返回 `rollover`
+/// * 单人模式下 `data` 为 `LotteryResult`，多人模式下没有唯一的赢家，不返回 `data`
+pub fn try_lottery(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    randomness: Option<Binary>,
+    proof: Vec<Binary>,
+) -> Result<Response, ContractError> {
+    assert_no_funds(&info)?;
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::Lottery,
+        &info.sender,
+        env.block.time,
+    )?;
+    if !state.has_player() {
+        return Err(ContractError::NotReady {});
+    }
+    // 同一个区块内开放并开奖便于操纵结果
+    if matches!(state.start_height, Some(height) if env.block.height < height + state.min_blocks) {
+        return Err(ContractError::TooSoon {});
+    }
+    // 截止下注后管理员超时没有揭示数字，按玩家猜对结算
+    let forfeit = state.reveal_expired(env.block.time);
+    match (state.beacon_root.as_ref(), randomness) {
+        (Some(root), Some(randomness)) => {
+            let leaf = beacon::leaf(state.rounds_started, &randomness);
+            if !beacon::verify_proof(root, leaf, &proof) {
+                return Err(ContractError::BadProof {});
+            }
+            state.guess_number = beacon::draw(&randomness, state.min_number, state.max_number);
+        }
+        (Some(_), None) => return Err(ContractError::NotRevealed {}),
+        (None, Some(_)) => return Err(ContractError::BadProof {}),
+        (None, None) => {}
+    }
+    state.is_lottery = true;
+    state.is_playing = false;
+    state.forfeited = forfeit;
+
+    // 托管中的奖金属于之前的赢家，佣金和备用金属于管理员，都不参与本轮分配
+    let balance = deps
+        .querier
+        .query_balance(&env.contract.address, &state.denom)?;
+    let payout = balance
+        .amount
+        .saturating_sub(state.escrowed + state.accrued_fees + state.reserve);
+    if state.multi_player {
+        return settle_bets(deps, env, state, payout, forfeit);
+    }
 
-    let res = Response::new();
+    let user = match state.user.clone() {
+        Some(user) => user,
+        None => return Err(ContractError::NotReady {}),
+    };
+    let user_won = state.user_won();
+    // 配置了倍数时赢家按本金的倍数获奖，奖金池剩下的部分归管理员
+    let prize = if user_won && state.win_multiplier_bps != 0 {
+        state
+            .user_payed
+            .multiply_ratio(state.win_multiplier_bps, 10_000u128)
+            .min(payout)
+    } else {
+        payout
+    };
+    let fee_waived =
+        record_game(deps.storage, &user, state.user_payed)? < u64::from(state.free_rounds);
+    let commission = if user_won && !fee_waived {
+        state
+            .bonus
+            .multiply_ratio(state.commission_bps, 10_000u128)
+            .min(prize)
+    } else {
+        Uint128::new(0)
+    };
 
-    let to_address = if state.guess_is_odd == (state.guess_number % 2 != 0) {
-        state.owner.to_string()
+    // 只有用户赢得的奖金需要托管
+    let (winner, delay_seconds) = if user_won {
+        (user, state.payout_delay_seconds)
     } else {
-        state.user.as_ref().unwrap().to_string()
+        record_loss(deps.storage, &user, state.user_payed, &env)?;
+        (state.owner.clone(), 0)
     };
+    let mut stats = FLOW_STATS.load(deps.storage)?;
+    // 用户获胜时庄家付出用户本金以外的部分（佣金留给庄家），用户失败时庄家得到用户的本金
+    if user_won {
+        stats.total_house_payouts += (prize - commission).saturating_sub(state.user_payed);
+    } else {
+        stats.total_house_income += state.user_payed;
+    }
+    // 庄家获胜且计入本轮后的庄家优势不低于下限时，奖金池留作下一轮的奖金
+    let rollover = !user_won
+        && !state.single_round_only
+        && matches!(state.rollover_min_edge_bps, Some(floor) if edge_bps(&stats) >= i128::from(floor));
 
-    let balance = deps.querier.query_balance(env.contract.address, DENOM)?;
+    let mut res = Response::new()
+        .add_attribute("action", "lottery")
+        .add_attribute("winner", &winner)
+        .add_attribute("payout", payout)
+        .add_attribute("commission", commission);
+    state.accrued_fees += commission;
+    let winner_amount = if rollover {
+        Uint128::new(0)
+    } else {
+        prize - commission
+    };
+    if rollover {
+        state.rollover_bonus = payout;
+        res = res.add_attribute("rollover", payout);
+    } else {
+        stats.total_paid_ever += payout;
+        if user_won {
+            record_win(deps.storage, &winner, winner_amount)?;
+        }
+        if let Some(msg) = pay_winner(
+            deps.storage,
+            &mut state,
+            &winner,
+            winner_amount,
+            delay_seconds,
+            env.block.time,
+        )? {
+            res = res.add_message(msg);
+        }
+        let house_share = payout - prize;
+        if !house_share.is_zero() {
+            res = res
+                .add_attribute("house_share", house_share)
+                .add_message(BankMsg::Send {
+                    to_address: state.owner.to_string(),
+                    amount: coins(house_share.u128(), &state.denom),
+                });
+        }
+    }
+    let result = LotteryResult {
+        guess_number: state.guess_number,
+        user_won,
+        winner: winner.clone(),
+        payout,
+        winner_amount,
+    };
+    record_round(deps.storage, &mut state, Some(winner))?;
+    STATE.save(deps.storage, &state)?;
+    FLOW_STATS.save(deps.storage, &stats)?;
 
-    Ok(res.add_message(BankMsg::Send {
-        to_address,
-        amount: vec![balance],
-    }))
+    Ok(res.set_data(to_binary(&result)?))
 }
 
-/// 重置游戏
+/// 多人模式下结算本轮的所有下注
 ///
-/// 管理员可在游戏完成后重置游戏状态以进行下一轮游戏
-pub fn try_reset(
+/// 猜对的玩家按下注金额的比例瓜分扣除佣金后的奖金池，没有人猜对且没有配置 `min_distinct_winners` 时奖金池全部归管理员
+/// * 按比例分配剩下的零头归管理员
+/// * 佣金只按没有免收佣金的赢家的下注比例抽取，累计在合约中由管理员提取
+/// * 猜对的人数少于 `min_distinct_winners` 时本轮作废，没有人猜对也一样，见 `void_bets`
+/// * 返回属性 `action`、`winners`、`payout`、`commission`
+fn settle_bets(
     deps: DepsMut,
-    info: MessageInfo,
-    number: i8,
     env: Env,
+    mut state: State,
+    payout: Uint128,
+    forfeit: bool,
 ) -> Result<Response, ContractError> {
-    let state = STATE.update(deps.storage, |mut state| -> Result<_, ContractError> {
-        if state.is_playing && state.user.is_some() {
-            return Err(ContractError::IsPlaying {});
-        }
-        if info.sender != state.owner {
-            return Err(ContractError::Unauthorized {});
-        }
-        state.user = None;
-        state.is_lottery = false;
-        state.is_playing = false;
-        state.guess_number = number;
-        state.bonus = Uint128::new(0);
-        Ok(state)
+    let is_odd = state.guess_number % 2 != 0;
+    let won = |bet: &Bet| forfeit || bet.is_odd == is_odd;
+    let bets = BETS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (key, bet) = item?;
+            let player = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+            Ok((Addr::unchecked(player), bet))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let winners = bets.iter().filter(|(_, bet)| won(bet)).count() as u32;
+    if winners < state.min_distinct_winners {
+        return void_bets(deps, state, bets, winners, payout);
+    }
+    let mut winning_stake = Uint128::new(0);
+    let mut charged_stake = Uint128::new(0);
+    for (player, bet) in bets.iter() {
+        let fee_waived =
+            record_game(deps.storage, player, bet.stake)? < u64::from(state.free_rounds);
+        if won(bet) {
+            winning_stake += bet.stake;
+            if !fee_waived {
+                charged_stake += bet.stake;
+            }
+        }
+    }
+    let commission = if !winning_stake.is_zero() {
+        state
+            .bonus
+            .multiply_ratio(state.commission_bps, 10_000u128)
+            .multiply_ratio(charged_stake, winning_stake)
+            .min(payout)
+    } else {
+        Uint128::new(0)
+    };
+    let pool = payout - commission;
+
+    let mut res = Response::new()
+        .add_attribute("action", "lottery")
+        .add_attribute("winners", winners.to_string())
+        .add_attribute("payout", payout)
+        .add_attribute("commission", commission);
+    let delay_seconds = state.payout_delay_seconds;
+    let mut paid = Uint128::new(0);
+    let mut house_income = Uint128::new(0);
+    let mut house_payouts = Uint128::new(0);
+    for (player, bet) in bets {
+        BETS.remove(deps.storage, &player);
+        if !won(&bet) {
+            record_loss(deps.storage, &player, bet.stake, &env)?;
+            house_income += bet.stake;
+            continue;
+        }
+        let share = if winning_stake.is_zero() {
+            Uint128::new(0)
+        } else {
+            pool.multiply_ratio(bet.stake, winning_stake)
+        };
+        paid += share;
+        house_payouts += share.saturating_sub(bet.stake);
+        record_win(deps.storage, &player, share)?;
+        if let Some(msg) = pay_winner(
+            deps.storage,
+            &mut state,
+            &player,
+            share,
+            delay_seconds,
+            env.block.time,
+        )? {
+            res = res.add_message(msg);
+        }
+    }
+    state.accrued_fees += commission;
+    let owner_amount = payout - paid - commission;
+    if !owner_amount.is_zero() {
+        let owner = state.owner.clone();
+        if let Some(msg) = pay_winner(
+            deps.storage,
+            &mut state,
+            &owner,
+            owner_amount,
+            0,
+            env.block.time,
+        )? {
+            res = res.add_message(msg);
+        }
+    }
+    state.player_count = 0;
+    record_round(deps.storage, &mut state, None)?;
+    STATE.save(deps.storage, &state)?;
+    FLOW_STATS.update(deps.storage, |mut stats| -> StdResult<_> {
+        stats.total_paid_ever += payout;
+        stats.total_house_income += house_income;
+        stats.total_house_payouts += house_payouts;
+        Ok(stats)
     })?;
-    let balance = deps.querier.query_all_balances(env.contract.address)?;
-    let mut res = Response::new();
-    if !balance.is_empty() {
+
+    Ok(res)
+}
+
+/// 作废多人模式的一轮
+///
+/// 退还每个玩家的下注金额，奖金池的其余部分转给管理员，不计入玩家的战绩
+/// * 返回属性 `action`、`winners`、`voided`、`payout`
+fn void_bets(
+    deps: DepsMut,
+    mut state: State,
+    bets: Vec<(Addr, Bet)>,
+    winners: u32,
+    payout: Uint128,
+) -> Result<Response, ContractError> {
+    let mut res = Response::new()
+        .add_attribute("action", "lottery")
+        .add_attribute("winners", winners.to_string())
+        .add_attribute("voided", "true")
+        .add_attribute("payout", payout);
+    let mut refunded = Uint128::new(0);
+    for (player, bet) in bets {
+        BETS.remove(deps.storage, &player);
+        refunded += bet.stake;
+        res = res.add_message(BankMsg::Send {
+            to_address: player.to_string(),
+            amount: coins(bet.stake.u128(), &state.denom),
+        });
+    }
+    let owner_amount = payout.saturating_sub(refunded);
+    if !owner_amount.is_zero() {
         res = res.add_message(BankMsg::Send {
             to_address: state.owner.to_string(),
-            amount: balance,
+            amount: coins(owner_amount.u128(), &state.denom),
         });
     }
+    state.player_count = 0;
+    record_round(deps.storage, &mut state, None)?;
+    STATE.save(deps.storage, &state)?;
+    FLOW_STATS.update(deps.storage, |mut stats| -> StdResult<_> {
+        stats.total_paid_ever += payout;
+        Ok(stats)
+    })?;
+
     Ok(res)
 }
 
-/// 查询操作
+/// 记录开奖结果，编号从1开始递增，同时更新单双分布
 ///
-/// 只对合约查询操作，无法修改合约信息
+/// 多人模式下可能有多个赢家，`winner` 为空
+fn record_round(
+    storage: &mut dyn Storage,
+    state: &mut State,
+    winner: Option<Addr>,
+) -> StdResult<()> {
+    state.round_count += 1;
+    DRAW_HISTOGRAM.update(storage, |mut histogram| -> StdResult<_> {
+        if state.guess_number % 2 != 0 {
+            histogram.odd += 1;
+        } else {
+            histogram.even += 1;
+        }
+        Ok(histogram)
+    })?;
+    let record = RoundRecord {
+        guess_number: state.guess_number,
+        guess_is_odd: state.guess_is_odd,
+        user: state.user.clone(),
+        bonus: state.bonus,
+        winner,
+        metadata: state.metadata.clone(),
+    };
+    ROUNDS.save(storage, U64Key::from(state.round_count), &record)
+}
+
+/// 记录地址参与了一轮开奖，返回之前参与的轮数
+fn record_game(storage: &mut dyn Storage, player: &Addr, wagered: Uint128) -> StdResult<u64> {
+    let mut stats = USER_STATS.may_load(storage, player)?.unwrap_or_default();
+    let played = stats.games_played;
+    stats.games_played += 1;
+    stats.total_wagered += wagered;
+    USER_STATS.save(storage, player, &stats)?;
+    Ok(played)
+}
+
+/// 记录地址猜对了一轮和赢得的奖金
+fn record_win(storage: &mut dyn Storage, player: &Addr, amount: Uint128) -> StdResult<()> {
+    USER_STATS.update(storage, player, |stats| -> StdResult<_> {
+        let mut stats = stats.unwrap_or_default();
+        stats.games_won += 1;
+        stats.total_won += amount;
+        Ok(stats)
+    })?;
+    Ok(())
+}
+
+/// 向赢家发放奖金
 ///
-/// ## 参数
-/// * Deps: 可以读存储 `Storage`
-/// * Env: 包含区块 `block` 和 合约信息 `contract`
-/// * QueryMsg: 自定义的信息
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetStatus {} => to_binary(&query_status(deps)?),
+/// 需要托管或者金额低于 `min_payout_amount` 时记入待领取余额，否则返回转账消息
+fn pay_winner(
+    storage: &mut dyn Storage,
+    state: &mut State,
+    winner: &Addr,
+    amount: Uint128,
+    delay_seconds: u64,
+    now: Timestamp,
+) -> StdResult<Option<BankMsg>> {
+    if delay_seconds == 0 && amount >= state.min_payout_amount {
+        return Ok(Some(BankMsg::Send {
+            to_address: winner.to_string(),
+            amount: coins(amount.u128(), &state.denom),
+        }));
     }
+    let claimable_after = now.plus_seconds(delay_seconds);
+    CLAIMS.update(storage, winner, |claim| -> StdResult<_> {
+        Ok(match claim {
+            Some(claim) => Claim {
+                amount: claim.amount + amount,
+                claimable_after: claim.claimable_after.max(claimable_after),
+            },
+            None => Claim {
+                amount,
+                claimable_after,
+            },
+        })
+    })?;
+    state.escrowed += amount;
+    Ok(None)
 }
 
-/// 查询游戏状态
+/// 领取奖金
 ///
-/// 返回游戏是否在进行和奖金金额
-fn query_status(deps: Deps) -> StdResult<StatusResponse> {
-    let state = STATE.load(deps.storage)?;
-    Ok(StatusResponse {
-        playing: state.is_playing,
-        bonus: state.bonus,
-    })
+/// 赢家在托管期满后领取托管的奖金，期满前领取返回 `CoolingDown`
+/// * 返回属性 `action`、`amount`
+pub fn try_claim(deps: DepsMut, info: MessageInfo, env: Env) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::Claim,
+        &info.sender,
+        env.block.time,
+    )?;
+    let claim = CLAIMS
+        .may_load(deps.storage, &info.sender)?
+        .ok_or(ContractError::NothingToClaim {})?;
+    if env.block.time < claim.claimable_after {
+        return Err(ContractError::CoolingDown {});
+    }
+    CLAIMS.remove(deps.storage, &info.sender);
+    state.escrowed = state
+        .escrowed
+        .checked_sub(claim.amount)
+        .map_err(StdError::from)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "claim")
+        .add_attribute("amount", claim.amount)
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: coins(claim.amount.u128(), &state.denom),
+        }))
 }
 
-// 用Mock模拟对合约进行单元测试
-#[cfg(test)]
+/// 恢复押注
+///
+/// 管理员补足余额后解除因余额不足导致的暂停
+/// * 余额仍不足以覆盖已记账的资金时返回 `Insolvent`
+/// * 返回属性 `action`
+pub fn try_resume(deps: DepsMut, info: MessageInfo, env: Env) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::Resume,
+        &info.sender,
+        env.block.time,
+    )?;
+    if !funding_shortfall(deps.as_ref(), &env, &state)?.is_zero() {
+        return Err(ContractError::Insolvent {});
+    }
+    state.paused = false;
+    state.pause_reason = None;
+    STATE.save(deps.storage, &state)?;
+    Ok(Response::new().add_attribute("action", "resume"))
+}
+
+/// 补充资金
+///
+/// 管理员向合约转入资金以覆盖应付的资金，转入的金额记为备用金，不计入奖金，开奖和提取零头时都不会转出
+/// * 补足后自动解除因余额不足导致的暂停
+/// * 不再需要的备用金由管理员通过 `WithdrawReserve` 提取
+/// * 返回属性 `action`、`amount`、`solvent`、`paused`
+pub fn try_top_up(deps: DepsMut, info: MessageInfo, env: Env) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::TopUp,
+        &info.sender,
+        env.block.time,
+    )?;
+    let pay = get_coin_u128(&info, &state.denom);
+    state.reserve += pay;
+    let solvent = funding_shortfall(deps.as_ref(), &env, &state)?.is_zero();
+    if solvent && state.paused {
+        state.paused = false;
+        state.pause_reason = None;
+    }
+    STATE.save(deps.storage, &state)?;
+    Ok(Response::new()
+        .add_attribute("action", "top_up")
+        .add_attribute("amount", pay)
+        .add_attribute("solvent", solvent.to_string())
+        .add_attribute("paused", state.paused.to_string()))
+}
+
+/// 提取佣金
+///
+/// 管理员提取累计的佣金，不影响进行中的一轮
+/// * 没有累计的佣金返回 `NoFees`
+/// * 返回属性 `action`、`amount`
+pub fn try_withdraw_fees(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    assert_no_funds(&info)?;
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::WithdrawFees,
+        &info.sender,
+        env.block.time,
+    )?;
+    if state.accrued_fees.is_zero() {
+        return Err(ContractError::NoFees {});
+    }
+    let amount = state.accrued_fees;
+    state.accrued_fees = Uint128::new(0);
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "withdraw_fees")
+        .add_attribute("amount", amount)
+        .add_message(BankMsg::Send {
+            to_address: state.owner.to_string(),
+            amount: coins(amount.u128(), &state.denom),
+        }))
+}
+
+/// 提取备用金
+///
+/// 管理员提取通过 `TopUp` 补充的备用金，用于弥补缺口的部分留在合约中
+/// * 没有可以提取的备用金返回 `NoReserve`
+/// * 附带资金返回 `UnexpectedFunds`
+/// * 返回属性 `action`、`amount`
+pub fn try_withdraw_reserve(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    assert_no_funds(&info)?;
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::WithdrawReserve,
+        &info.sender,
+        env.block.time,
+    )?;
+    let balance = deps
+        .querier
+        .query_balance(&env.contract.address, &state.denom)?
+        .amount;
+    let amount = balance
+        .saturating_sub(state.owed_funds())
+        .min(state.reserve);
+    if amount.is_zero() {
+        return Err(ContractError::NoReserve {});
+    }
+    state.reserve -= amount;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "withdraw_reserve")
+        .add_attribute("amount", amount)
+        .add_message(BankMsg::Send {
+            to_address: state.owner.to_string(),
+            amount: coins(amount.u128(), &state.denom),
+        }))
+}
+
+/// 提名新管理员
+///
+/// 管理员提名新的管理员，新管理员调用 `AcceptOwnership` 后才完成交接
+/// * 再次提名会覆盖之前的提名
+/// * 附带资金返回 `UnexpectedFunds`
+/// * 返回属性 `action`、`proposed_owner`
+pub fn try_transfer_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    new_owner: String,
+) -> Result<Response, ContractError> {
+    assert_no_funds(&info)?;
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::TransferOwnership,
+        &info.sender,
+        env.block.time,
+    )?;
+    let new_owner = deps.api.addr_validate(&new_owner)?;
+    state.proposed_owner = Some(new_owner.clone());
+    STATE.save(deps.storage, &state)?;
+    Ok(Response::new()
+        .add_attribute("action", "transfer_ownership")
+        .add_attribute("proposed_owner", new_owner))
+}
+
+/// 接受管理员身份
+///
+/// 只有被提名的地址可以调用，调用后成为新的管理员
+/// * 返回属性 `action`、`owner`
+pub fn try_accept_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::AcceptOwnership,
+        &info.sender,
+        env.block.time,
+    )?;
+    state.owner = info.sender.clone();
+    state.proposed_owner = None;
+    STATE.save(deps.storage, &state)?;
+    Ok(Response::new()
+        .add_attribute("action", "accept_ownership")
+        .add_attribute("owner", info.sender))
+}
+
+/// 余额不足时返回缺少的金额，未开启 `pause_on_insolvency` 时不检查
+fn insolvency_shortfall(deps: Deps, env: &Env, state: &State) -> StdResult<Option<Uint128>> {
+    if !state.pause_on_insolvency {
+        return Ok(None);
+    }
+    let shortfall = funding_shortfall(deps, env, state)?;
+    Ok(if shortfall.is_zero() {
+        None
+    } else {
+        Some(shortfall)
+    })
+}
+
+/// 应付的资金超出合约余额的部分，备用金用于弥补缺口，不计入应付的资金
+fn funding_shortfall(deps: Deps, env: &Env, state: &State) -> StdResult<Uint128> {
+    let balance = deps
+        .querier
+        .query_balance(&env.contract.address, &state.denom)?
+        .amount;
+    Ok(state.owed_funds().saturating_sub(balance))
+}
+
+/// 暂停押注并记录原因
+fn pause_for_insolvency(state: &mut State, shortfall: Uint128) -> Event {
+    state.paused = true;
+    state.pause_reason = Some(format!("insolvency detected, short by {}", shortfall));
+    Event::new("insolvency_detected").add_attribute("shortfall", shortfall)
+}
+
+/// 重置游戏
+///
+/// 管理员可在游戏完成后重置游戏状态以进行下一轮游戏
+/// * 单轮模式下开奖后合约退役，不能再重置
+/// * 重置后需要重新提交本轮数字的承诺
+/// * 配置了 `block_reset_until_claimed` 时，还有待领取的奖金返回 `UnclaimedWinnings`
+/// * 上一轮留下的奖金池作为本轮的奖金
+/// * 重置不转出任何资金，合约中剩余的零头由管理员通过 `CollectDust` 提取
+/// * 返回属性 `action`
+pub fn try_reset(deps: DepsMut, info: MessageInfo, env: Env) -> Result<Response, ContractError> {
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::Reset,
+        &info.sender,
+        env.block.time,
+    )?;
+    // 已有玩家下注时不能重置
+    if state.is_playing && state.has_player() {
+        return Err(ContractError::IsPlaying {});
+    }
+    if state.single_round_only && state.is_lottery {
+        return Err(ContractError::Retired {});
+    }
+    if state.block_reset_until_claimed && !state.escrowed.is_zero() {
+        return Err(ContractError::UnclaimedWinnings {});
+    }
+    clear_round(&mut state);
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_attribute("action", "reset"))
+}
+
+/// 提取零头
+///
+/// 管理员提取合约中没有记账的余额，已记账的资金（奖金、下注、托管、佣金等）留在合约中
+/// * 任何阶段都可以提取，不影响进行中的一轮
+/// * 投注币种以外的币种全部转出
+/// * 附带资金返回 `UnexpectedFunds`
+/// * 返回属性 `action`
+pub fn try_collect_dust(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    assert_no_funds(&info)?;
+    let state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::CollectDust,
+        &info.sender,
+        env.block.time,
+    )?;
+    let locked = state.locked_funds();
+    let balance: Vec<Coin> = deps
+        .querier
+        .query_all_balances(env.contract.address)?
+        .into_iter()
+        .map(|mut coin| {
+            if coin.denom == state.denom {
+                coin.amount = coin.amount.saturating_sub(locked);
+            }
+            coin
+        })
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+    let mut res = Response::new().add_attribute("action", "collect_dust");
+    if !balance.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: state.owner.to_string(),
+            amount: balance,
+        });
+    }
+    Ok(res)
+}
+
+/// 清空本轮的状态，回到开放投注前的阶段
+fn clear_round(state: &mut State) {
+    state.user = None;
+    state.is_lottery = false;
+    state.is_playing = false;
+    // 上一轮留下的奖金池作为本轮的奖金
+    state.bonus = state.rollover_bonus;
+    state.rollover_bonus = Uint128::new(0);
+    state.guess_number = 0;
+    state.guessed_number = 0;
+    state.commit_hash = None;
+    state.revealed = false;
+    state.user_payed = Uint128::new(0);
+    state.metadata = None;
+    state.round_started_at = None;
+    state.start_height = None;
+    state.bet_complete = false;
+    state.forfeited = false;
+    state.bet_deadline = None;
+    state.bets_placed = 0;
+    state.player_count = 0;
+}
+
+/// 取消本轮
+///
+/// 开奖前中止本轮，不开奖，退还玩家的下注，奖金退还给管理员
+/// * 还没有玩家下注时由管理员取消
+/// * 玩家下注后管理员已经知道本轮的数字，不能再取消，返回 `Unauthorized`；已下注的玩家可以在揭示前取消，多人模式下退还所有玩家的下注
+/// * 单人模式下截止下注时玩家还没有付清，任何人都可以取消
+/// * 配置了 `beacon_root` 时，截止下注后玩家不能再取消已付清的下注，返回 `BettingClosed`
+/// * 已开奖返回 `RoundSettled`
+/// * 取消后需要重新提交本轮数字的承诺
+/// * 附带资金返回 `UnexpectedFunds`
+/// * 返回属性 `action`、`refunded`、`bonus`
+pub fn try_cancel_round(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    assert_no_funds(&info)?;
+    let mut state = STATE.load(deps.storage)?;
+    authorize(
+        deps.storage,
+        &state,
+        Action::CancelRound,
+        &info.sender,
+        env.block.time,
+    )?;
+    let refunds = if state.multi_player {
+        let bets = BETS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                let (key, bet) = item?;
+                let player = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+                Ok((Addr::unchecked(player), bet.stake))
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+        for (player, _) in bets.iter() {
+            BETS.remove(deps.storage, player);
+        }
+        bets
+    } else {
+        state
+            .user
+            .clone()
+            .map(|user| vec![(user, state.user_payed)])
+            .unwrap_or_default()
+    };
+
+    let bonus = state.bonus;
+    let mut refunded = Uint128::new(0);
+    let mut res = Response::new().add_attribute("action", "cancel_round");
+    for (player, stake) in refunds.into_iter().filter(|(_, stake)| !stake.is_zero()) {
+        refunded += stake;
+        res = res.add_message(BankMsg::Send {
+            to_address: player.to_string(),
+            amount: coins(stake.u128(), &state.denom),
+        });
+    }
+    if !bonus.is_zero() {
+        res = res.add_message(BankMsg::Send {
+            to_address: state.owner.to_string(),
+            amount: coins(bonus.u128(), &state.denom),
+        });
+    }
+    clear_round(&mut state);
+    STATE.save(deps.storage, &state)?;
+    FLOW_STATS.update(deps.storage, |mut stats| -> StdResult<_> {
+        stats.total_paid_ever += refunded + bonus;
+        Ok(stats)
+    })?;
+
+    Ok(res
+        .add_attribute("refunded", refunded)
+        .add_attribute("bonus", bonus))
+}
+
+/// 查询操作
+///
+/// 只对合约查询操作，无法修改合约信息
+///
+/// ## 参数
+/// * Deps: 可以读存储 `Storage`
+/// * Env: 包含区块 `block` 和 合约信息 `contract`
+/// * QueryMsg: 自定义的信息
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetStatus {} => to_binary(&query_status(deps)?),
+        QueryMsg::GetStatusCompact {} => to_binary(&query_status_compact(deps)?),
+        QueryMsg::GetActionSpec {} => to_binary(&query_action_spec(deps, env)?),
+        QueryMsg::BuildAction { action } => to_binary(&query_build_action(deps, env, action)?),
+        QueryMsg::GetTransitionGraph {} => to_binary(&query_transition_graph(deps, env)?),
+        QueryMsg::GetFlowStats {} => to_binary(&query_flow_stats(deps)?),
+        QueryMsg::CheckAuthorization { address, action } => {
+            to_binary(&query_check_authorization(deps, env, address, action)?)
+        }
+        QueryMsg::Reconcile {} => to_binary(&query_reconcile(deps, env)?),
+        QueryMsg::GetLiabilities {} => to_binary(&query_liabilities(deps)?),
+        QueryMsg::GetSuggestedBonus {} => to_binary(&query_suggested_bonus(deps)?),
+        QueryMsg::GetStateDigest {} => to_binary(&query_state_digest(deps)?),
+        QueryMsg::GetAllBalances {} => to_binary(&query_all_balances(deps, env)?),
+        QueryMsg::GetBalance {} => to_binary(&query_balance(deps, env)?),
+        QueryMsg::IsWorstCaseSolvent {} => to_binary(&query_solvency(deps, env)?),
+        QueryMsg::GetRealizedEdge {} => to_binary(&query_realized_edge(deps)?),
+        QueryMsg::GetState {} => to_binary(&query_full_state(deps)?),
+        QueryMsg::GetPauseReason {} => to_binary(&query_pause_reason(deps)?),
+        QueryMsg::GetRoundMetrics {} => to_binary(&query_round_metrics(deps, env)?),
+        QueryMsg::GetCommitmentPreimageFormat {} => to_binary(&query_commitment_format()),
+        QueryMsg::GetDrawHistogram {} => to_binary(&DRAW_HISTOGRAM.load(deps.storage)?),
+        QueryMsg::GetLimits {} => to_binary(&query_limits(deps)?),
+        QueryMsg::GetConfig {} => to_binary(&query_config(deps)?),
+        QueryMsg::GetBet {} => to_binary(&query_bet(deps)?),
+        QueryMsg::GetResult {} => to_binary(&query_result(deps)?),
+        QueryMsg::GetRound { id } => to_binary(&ROUNDS.load(deps.storage, U64Key::from(id))?),
+        QueryMsg::ListRounds { start_after, limit } => {
+            to_binary(&query_list_rounds(deps, start_after, limit)?)
+        }
+        QueryMsg::GetUserStats { address } => to_binary(&query_user_stats(deps, address)?),
+    }
+}
+
+/// 查询游戏状态
+///
+/// 返回游戏是否在进行和奖金金额
+fn query_status(deps: Deps) -> StdResult<StatusResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(StatusResponse {
+        playing: state.is_playing,
+        bonus: state.bonus,
+        metadata: state.metadata,
+    })
+}
+
+/// 查询紧凑编码的合约状态，供频繁轮询的客户端使用
+///
+/// 共 25 字节，整数均为大端序：
+///
+/// | 偏移 | 长度 | 内容 |
+/// |------|------|------|
+/// | 0 | 1 | 游戏阶段，0 准备中、1 投注中、2 已开奖 |
+/// | 1 | 16 | 奖金，`u128` |
+/// | 17 | 8 | 本轮截止下注时间的秒数，`u64`，0 表示没有截止时间 |
+fn query_status_compact(deps: Deps) -> StdResult<Binary> {
+    let state = STATE.load(deps.storage)?;
+    let phase: u8 = match state.phase() {
+        Phase::Setup => 0,
+        Phase::Betting => 1,
+        Phase::Resolved => 2,
+    };
+    let deadline = state.bet_deadline.map_or(0, |deadline| deadline.seconds());
+    let mut status = Vec::with_capacity(25);
+    status.push(phase);
+    status.extend_from_slice(&state.bonus.u128().to_be_bytes());
+    status.extend_from_slice(&deadline.to_be_bytes());
+    Ok(Binary(status))
+}
+
+/// 查询完整的游戏状态
+///
+/// 为了不泄露本轮的数字，只有开奖后才返回 `guess_number`
+fn query_full_state(deps: Deps) -> StdResult<FullStateResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(FullStateResponse {
+        guess_number: if state.is_lottery {
+            Some(state.guess_number)
+        } else {
+            None
+        },
+        guess_is_odd: state.guess_is_odd,
+        user: state.user,
+        user_payed: state.user_payed,
+        is_lottery: state.is_lottery,
+        owner: state.owner,
+    })
+}
+
+/// 查询暂停押注的原因
+fn query_pause_reason(deps: Deps) -> StdResult<PauseReasonResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(PauseReasonResponse {
+        paused: state.paused,
+        reason: state.pause_reason,
+    })
+}
+
+/// 查询本轮的实时统计
+///
+/// 从开放投注开始计时，还没有开放投注时全部为0
+fn query_round_metrics(deps: Deps, env: Env) -> StdResult<RoundMetricsResponse> {
+    let state = STATE.load(deps.storage)?;
+    let elapsed_seconds = match state.round_started_at {
+        Some(started_at) => env
+            .block
+            .time
+            .seconds()
+            .saturating_sub(started_at.seconds()),
+        None => 0,
+    };
+    let bets_per_minute = if elapsed_seconds == 0 {
+        Decimal::zero()
+    } else {
+        Decimal::from_ratio(state.bets_placed * 60, elapsed_seconds)
+    };
+    Ok(RoundMetricsResponse {
+        elapsed_seconds,
+        bets_placed: state.bets_placed,
+        total_staked: state.user_payed,
+        bets_per_minute,
+    })
+}
+
+/// 查询承诺的计算方式
+///
+/// 和 `commitment_hash` 的实现保持一致
+fn query_commitment_format() -> CommitmentFormatResponse {
+    CommitmentFormatResponse {
+        fields: vec!["secret".to_string(), "nonce".to_string()],
+        separator: ":".to_string(),
+        secret_format: "canonical decimal i8, no leading zeros or plus sign".to_string(),
+        encoding: "utf8".to_string(),
+        hash: "sha256".to_string(),
+        output: "lowercase hex".to_string(),
+    }
+}
+
+/// 查询本轮的开奖结果
+///
+/// 开奖后才返回结果，多人模式下没有唯一的赢家，总是返回空
+fn query_result(deps: Deps) -> StdResult<Option<ResultResponse>> {
+    let state = STATE.load(deps.storage)?;
+    if !state.is_lottery {
+        return Ok(None);
+    }
+    let user_won = state.user_won();
+    Ok(state.user.clone().map(|user| ResultResponse {
+        user_won,
+        winner: if user_won { user } else { state.owner.clone() },
+        guess_number: state.guess_number,
+    }))
+}
+
+/// 查询本轮玩家的下注
+///
+/// 多人模式下不记录单个玩家，总是返回空
+fn query_bet(deps: Deps) -> StdResult<Option<BetResponse>> {
+    let state = STATE.load(deps.storage)?;
+    let (guess_is_odd, paid) = (state.guess_is_odd, state.user_payed);
+    Ok(state.user.map(|user| BetResponse {
+        user,
+        guess_is_odd,
+        paid,
+    }))
+}
+
+/// 查询地址的累计统计
+fn query_user_stats(deps: Deps, address: String) -> StdResult<UserStats> {
+    let address = deps.api.addr_validate(&address)?;
+    Ok(USER_STATS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default())
+}
+
+/// 查询合约的配置
+///
+/// 返回初始化时设置的所有参数，没有设置的参数为实际使用的默认值
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(ConfigResponse {
+        owner: state.owner,
+        single_round_only: state.single_round_only,
+        payout_delay_seconds: state.payout_delay_seconds,
+        sweep_stray_into_bonus: state.sweep_stray_into_bonus,
+        min_payout_amount: state.min_payout_amount,
+        match_tolerance: state.match_tolerance,
+        bonus_growth_bps_per_round: state.bonus_growth_bps_per_round,
+        lottery_caller: state.lottery_caller,
+        max_metadata_len: state.max_metadata_len,
+        min_seconds_between_bonus: state.min_seconds_between_bonus,
+        denom: state.denom,
+        pause_on_insolvency: state.pause_on_insolvency,
+        commission_bps: state.commission_bps,
+        daily_loss_limit: state.daily_loss_limit,
+        beacon_root: state.beacon_root,
+        multi_player: state.multi_player,
+        free_rounds: state.free_rounds,
+        min_number: state.min_number,
+        max_number: state.max_number,
+        block_reset_until_claimed: state.block_reset_until_claimed,
+        allow_guess_for: state.allow_guess_for,
+        guess_mode: state.guess_mode,
+        min_bonus: state.min_bonus,
+        max_bonus: state.max_bonus,
+        rollover_min_edge_bps: state.rollover_min_edge_bps,
+        bet_duration_seconds: state.bet_duration_seconds,
+        min_distinct_winners: state.min_distinct_winners,
+        win_multiplier_bps: state.win_multiplier_bps,
+        min_blocks: state.min_blocks,
+        max_players: state.max_players,
+        reveal_timeout_seconds: state.reveal_timeout_seconds,
+    })
+}
+
+/// 查询合约配置的数值限制
+fn query_limits(deps: Deps) -> StdResult<LimitsResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(LimitsResponse {
+        match_tolerance: state.match_tolerance,
+        min_payout_amount: state.min_payout_amount,
+        daily_loss_limit: state.daily_loss_limit,
+        commission_bps: state.commission_bps,
+        max_metadata_len: state.max_metadata_len,
+        min_seconds_between_bonus: state.min_seconds_between_bonus,
+        min_number: state.min_number,
+        max_number: state.max_number,
+        min_bonus: state.min_bonus,
+        max_bonus: state.max_bonus,
+    })
+}
+
+/// 分页查询历史开奖记录
+///
+/// 按编号从小到大返回 `start_after` 之后的记录，默认每页 10 条，最多 30 条
+fn query_list_rounds(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<RoundsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive_int);
+    let rounds = ROUNDS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, record) = item?;
+            let id = key
+                .as_slice()
+                .try_into()
+                .map_err(|_| StdError::generic_err("Invalid round id"))?;
+            Ok((u64::from_be_bytes(id), record))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(RoundsResponse { rounds })
+}
+
+/// 查询操作规则
+///
+/// 返回每个执行操作在当前状态下可执行的阶段和调用者，由 `Action::rule` 生成
+fn query_action_spec(deps: Deps, env: Env) -> StdResult<ActionSpecResponse> {
+    let state = STATE.load(deps.storage)?;
+    let actions = Action::ALL
+        .iter()
+        .map(|action| {
+            let (phases, caller) = action.rule(&state, env.block.time);
+            ActionSpec {
+                action: *action,
+                phases: phases.to_vec(),
+                caller,
+            }
+        })
+        .collect();
+    Ok(ActionSpecResponse { actions })
+}
+
+/// 查询阶段转换图
+///
+/// 由 `Action::rule` 的阶段、`Action::guards` 的条件和 `Action::transition` 生成，只包含当前配置下可以执行的操作
+/// * 没有配置 `beacon_root` 时，开放投注需要已提交承诺，开奖需要已揭示数字；配置了时开奖需要已截止下注
+/// * 单轮模式下开奖后合约退役，不能再重置
+fn query_transition_graph(deps: Deps, env: Env) -> StdResult<TransitionGraphResponse> {
+    let state = STATE.load(deps.storage)?;
+    let mut transitions = vec![];
+    for action in Action::ALL.iter().copied() {
+        if !action.enabled(&state) {
+            continue;
+        }
+        let (phases, _) = action.rule(&state, env.block.time);
+        for from in phases.iter().copied() {
+            if action == Action::Reset && from == Phase::Resolved && state.single_round_only {
+                continue;
+            }
+            transitions.push(Transition {
+                from,
+                action,
+                to: action.transition(from),
+                guards: action.guards(&state),
+            });
+        }
+    }
+    Ok(TransitionGraphResponse { transitions })
+}
+
+/// 查询执行操作的消息模板
+///
+/// 使用和执行函数相同的 `check_ready` 判断现在能否执行，不检查调用者，消息中的参数使用占位值
+/// * 下注附带本轮的奖金额，单人模式下已有玩家时附带该玩家还需要支付的金额
+/// * 添加奖金附带建议奖金，补充余额附带缺少的金额
+/// * 操作名称不存在时返回错误
+fn query_build_action(deps: Deps, env: Env, name: String) -> StdResult<BuildActionResponse> {
+    let state = STATE.load(deps.storage)?;
+    let action = Action::from_name(&name)
+        .ok_or_else(|| StdError::generic_err(format!("Unknown action: {}", name)))?;
+    // 单人模式下当前玩家只需要补足剩下的金额
+    let stake = if state.multi_player || state.user.is_none() {
+        state.bonus
+    } else {
+        state.bonus.saturating_sub(state.user_payed)
+    };
+    let (msg, amount) = match action {
+        Action::Guess => (ExecuteMsg::Guess { is_odd: false }, stake),
+        Action::GuessFor => (
+            ExecuteMsg::GuessFor {
+                beneficiary: String::new(),
+                is_odd: false,
+            },
+            state.bonus,
+        ),
+        Action::GuessExact => (
+            ExecuteMsg::GuessExact {
+                number: state.min_number,
+            },
+            stake,
+        ),
+        Action::AddBonus => (ExecuteMsg::AddBonus {}, state.suggested_bonus),
+        Action::TopUp => (ExecuteMsg::TopUp {}, funding_shortfall(deps, &env, &state)?),
+        Action::Reset => (ExecuteMsg::Reset {}, Uint128::new(0)),
+        Action::CancelRound => (ExecuteMsg::CancelRound {}, Uint128::new(0)),
+        Action::Start => (ExecuteMsg::Start { metadata: None }, Uint128::new(0)),
+        Action::Lottery => (
+            ExecuteMsg::Lottery {
+                randomness: None,
+                proof: vec![],
+            },
+            Uint128::new(0),
+        ),
+        Action::Claim => (ExecuteMsg::Claim {}, Uint128::new(0)),
+        Action::Resume => (ExecuteMsg::Resume {}, Uint128::new(0)),
+        Action::WithdrawFees => (ExecuteMsg::WithdrawFees {}, Uint128::new(0)),
+        Action::CollectDust => (ExecuteMsg::CollectDust {}, Uint128::new(0)),
+        Action::WithdrawReserve => (ExecuteMsg::WithdrawReserve {}, Uint128::new(0)),
+        Action::Commit => (
+            ExecuteMsg::Commit {
+                hash: String::new(),
+            },
+            Uint128::new(0),
+        ),
+        Action::Reveal => (
+            ExecuteMsg::Reveal {
+                secret: String::new(),
+                nonce: String::new(),
+            },
+            Uint128::new(0),
+        ),
+        Action::TransferOwnership => (
+            ExecuteMsg::TransferOwnership {
+                new_owner: String::new(),
+            },
+            Uint128::new(0),
+        ),
+        Action::AcceptOwnership => (ExecuteMsg::AcceptOwnership {}, Uint128::new(0)),
+    };
+    let funds = if amount.is_zero() {
+        vec![]
+    } else {
+        coins(amount.u128(), &state.denom)
+    };
+    Ok(BuildActionResponse {
+        action,
+        allowed: check_ready(&state, action, env.block.time).is_ok(),
+        msg,
+        funds,
+    })
+}
+
+/// 查询资金流水
+///
+/// 返回累计下注、累计奖金、累计发放和当前锁定在合约中的金额
+fn query_flow_stats(deps: Deps) -> StdResult<FlowStatsResponse> {
+    let state = STATE.load(deps.storage)?;
+    let stats = FLOW_STATS.load(deps.storage)?;
+    Ok(FlowStatsResponse {
+        total_staked_ever: stats.total_staked_ever,
+        total_bonus_ever: stats.total_bonus_ever,
+        total_paid_ever: stats.total_paid_ever,
+        currently_locked: state.locked_funds(),
+    })
+}
+
+/// 查询权限
+///
+/// 使用和执行函数相同的 `authorize` 检查地址现在能否执行操作
+fn query_check_authorization(
+    deps: Deps,
+    env: Env,
+    address: String,
+    action: String,
+) -> StdResult<AuthorizationResponse> {
+    let state = STATE.load(deps.storage)?;
+    let address = deps.api.addr_validate(&address)?;
+    let action = Action::from_name(&action)
+        .ok_or_else(|| StdError::generic_err(format!("Unknown action: {}", action)))?;
+    Ok(AuthorizationResponse {
+        authorized: is_authorized(deps.storage, &state, action, &address, env.block.time),
+    })
+}
+
+/// 对账
+///
+/// 对比已记账的金额和合约实际余额，差额不为0说明有额外转入的资金或记账错误
+fn query_reconcile(deps: Deps, env: Env) -> StdResult<ReconcileResponse> {
+    let state = STATE.load(deps.storage)?;
+    let tracked = state.locked_funds();
+    let actual = deps
+        .querier
+        .query_balance(env.contract.address, &state.denom)?
+        .amount;
+    Ok(ReconcileResponse {
+        tracked,
+        actual,
+        drift: signed_diff(actual, tracked),
+    })
+}
+
+/// 查询建议奖金
+///
+/// 返回奖金基数、已开放的轮数和下一轮的建议奖金
+fn query_suggested_bonus(deps: Deps) -> StdResult<SuggestedBonusResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(SuggestedBonusResponse {
+        base_bonus: state.base_bonus,
+        rounds_started: state.rounds_started,
+        suggested_bonus: state.suggested_bonus,
+    })
+}
+
+/// 查询状态摘要
+///
+/// 返回序列化后的合约状态的 SHA-256，状态不变时摘要不变，用于监控状态是否被意外修改
+fn query_state_digest(deps: Deps) -> StdResult<StateDigestResponse> {
+    let state = STATE.load(deps.storage)?;
+    let digest = Sha256::digest(&to_vec(&state)?);
+    Ok(StateDigestResponse {
+        digest: hex::encode(digest),
+    })
+}
+
+/// 查询合约持有的所有币种余额
+fn query_all_balances(deps: Deps, env: Env) -> StdResult<AllBalancesResponse> {
+    let balances = deps.querier.query_all_balances(env.contract.address)?;
+    Ok(AllBalancesResponse { balances })
+}
+
+/// 查询合约持有的投注币种余额
+fn query_balance(deps: Deps, env: Env) -> StdResult<BalanceResponse> {
+    let state = STATE.load(deps.storage)?;
+    let balance = deps
+        .querier
+        .query_balance(env.contract.address, state.denom)?;
+    Ok(BalanceResponse {
+        denom: balance.denom,
+        amount: balance.amount,
+    })
+}
+
+/// 查询合约的负债
+///
+/// 返回每个地址可能需要支付的金额：本轮玩家获胜时的奖金和待领取的奖金
+fn query_liabilities(deps: Deps) -> StdResult<LiabilitiesResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(LiabilitiesResponse {
+        liabilities: liabilities(deps, &state)?,
+    })
+}
+
+/// 查询实际的庄家优势
+///
+/// 按 `(庄家收入 - 庄家支出) * 10000 / 用户累计下注` 计算，单位为万分之一，可以为负数
+fn query_realized_edge(deps: Deps) -> StdResult<RealizedEdgeResponse> {
+    let stats = FLOW_STATS.load(deps.storage)?;
+    Ok(RealizedEdgeResponse {
+        total_house_income: stats.total_house_income,
+        total_house_payouts: stats.total_house_payouts,
+        total_staked: stats.total_staked_ever,
+        edge_bps: edge_bps(&stats).to_string(),
+    })
+}
+
+/// 实际的庄家优势，单位为万分之一，还没有下注时为0
+fn edge_bps(stats: &FlowStats) -> i128 {
+    let staked = stats.total_staked_ever.u128() as i128;
+    if staked == 0 {
+        return 0;
+    }
+    let net = stats.total_house_income.u128() as i128 - stats.total_house_payouts.u128() as i128;
+    net * 10_000 / staked
+}
+
+/// 查询合约在最坏情况下能否偿付
+///
+/// 所有负债同时兑付时，合约余额不足的部分为缺口
+fn query_solvency(deps: Deps, env: Env) -> StdResult<SolvencyResponse> {
+    let state = STATE.load(deps.storage)?;
+    let shortfall = shortfall(deps, &env, &state)?;
+    Ok(SolvencyResponse {
+        solvent: shortfall.is_zero(),
+        shortfall,
+    })
+}
+
+/// 计算所有负债超出合约余额的部分
+fn shortfall(deps: Deps, env: &Env, state: &State) -> StdResult<Uint128> {
+    let total = liabilities(deps, state)?
+        .into_iter()
+        .fold(Uint128::new(0), |total, (_, amount)| total + amount);
+    let balance = deps
+        .querier
+        .query_balance(&env.contract.address, &state.denom)?
+        .amount;
+    Ok(total.saturating_sub(balance))
+}
+
+/// 汇总每个地址的负债
+fn liabilities(deps: Deps, state: &State) -> StdResult<Vec<(Addr, Uint128)>> {
+    let mut liabilities = CLAIMS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (key, claim) = item?;
+            let address = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+            Ok((Addr::unchecked(address), claim.amount))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    // 玩家已下注时，获胜可以拿走整个奖金池
+    let mut potentials = vec![];
+    if let (Phase::Betting, Some(user)) = (state.phase(), state.user.as_ref()) {
+        potentials.push((user.clone(), state.bonus + state.user_payed));
+    }
+    // 多人模式下按所有玩家都猜对时各自分到的金额计算，零头记在最后一个玩家，总额等于整个奖金池
+    if state.phase() == Phase::Betting && state.multi_player && !state.user_payed.is_zero() {
+        let bets = BETS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        let mut remaining = state.bonus;
+        for (index, (key, bet)) in bets.iter().enumerate() {
+            let player = String::from_utf8(key.clone()).map_err(StdError::invalid_utf8)?;
+            let share = if index + 1 == bets.len() {
+                remaining
+            } else {
+                state.bonus.multiply_ratio(bet.stake, state.user_payed)
+            };
+            remaining -= share;
+            potentials.push((Addr::unchecked(player), bet.stake + share));
+        }
+    }
+    for (player, potential) in potentials {
+        match liabilities
+            .iter_mut()
+            .find(|(address, _)| *address == player)
+        {
+            Some((_, amount)) => *amount += potential,
+            None => liabilities.push((player, potential)),
+        }
+    }
+    Ok(liabilities)
+}
+
+// 用Mock模拟对合约进行单元测试
+#[cfg(test)]
 mod tests {
     use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_binary};
+    use crate::action::{Caller, Guard};
+    use crate::state::{LotteryCaller, StateV0_1};
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+        MOCK_CONTRACT_ADDR,
+    };
+    use cosmwasm_std::{attr, coin, from_binary, CosmosMsg, OwnedDeps};
+
+    type MockDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+    // 测试用的随机数
+    const NONCE: &str = "nonce";
+
+    // 管理员提交本轮数字的承诺
+    fn commit_number(deps: &mut MockDeps, num: i8) {
+        let hash = commitment_hash(&num.to_string(), NONCE);
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Commit { hash }).unwrap();
+    }
+
+    // 管理员揭示本轮的数字
+    fn reveal_number(deps: &mut MockDeps, num: i8) {
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reveal {
+            secret: num.to_string(),
+            nonce: NONCE.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    // 管理员添加奖金并开放投注，还没有提交承诺时本轮的数字为0
+    fn open_round(deps: &mut MockDeps, bonus: u128) {
+        if STATE.load(&deps.storage).unwrap().commit_hash.is_none() {
+            commit_number(deps, 0);
+        }
+        let info = mock_info("creator", &coins(bonus, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        let info = mock_info("creator", &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Start { metadata: None },
+        )
+        .unwrap();
+    }
+
+    // 用户下注
+    fn place_guess(deps: &mut MockDeps, user: &str, amount: u128, is_odd: bool) -> Response {
+        let info = mock_info(user, &coins(amount, DEFAULT_DENOM));
+        let msg = ExecuteMsg::Guess { is_odd };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap()
+    }
+
+    // 开奖
+    fn run_lottery(deps: &mut MockDeps, sender: &str) -> Response {
+        let info = mock_info(sender, &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Lottery {
+                randomness: None,
+                proof: vec![],
+            },
+        )
+        .unwrap()
+    }
+
+    // 测试初始化
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies(&coins(1000, DEFAULT_DENOM));
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &coins(1000, DEFAULT_DENOM));
+
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
+        let value: StatusResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.bonus.u128());
+    }
+
+    #[test]
+    fn set_bonus() {
+        let mut deps = mock_dependencies(&coins(1000, DEFAULT_DENOM));
+
+        // 初始化合约
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &coins(200, DEFAULT_DENOM));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 管理员设置奖金为200
+        let info = mock_info("creator", &coins(200, DEFAULT_DENOM));
+        let msg = ExecuteMsg::AddBonus {};
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 查询奖金是否200
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
+        let value: StatusResponse = from_binary(&res).unwrap();
+        assert_eq!(200, value.bonus.u128());
+    }
+
+    // 测试重置合约
+    #[test]
+    fn reset() {
+        let mut deps = mock_dependencies(&coins(2000, DEFAULT_DENOM));
+
+        // 初始化合约
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &coins(2, DEFAULT_DENOM));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 设置奖金为200
+        let info = mock_info("creator", &coins(200, DEFAULT_DENOM));
+        let msg = ExecuteMsg::AddBonus {};
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 测试奖金是否200
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
+        let value: StatusResponse = from_binary(&res).unwrap();
+        assert_eq!(200, value.bonus.u128());
+
+        // 测试其他用户能否重置合约状态
+        let unauth_info = mock_info("anyone", &coins(2, DEFAULT_DENOM));
+        let msg = ExecuteMsg::Reset {};
+        let res = execute(deps.as_mut(), mock_env(), unauth_info, msg);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // 管理员重置合约
+        let auth_info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reset {};
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        // 重置后奖金应为0
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
+        let value: StatusResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.bonus.u128());
+    }
+
+    // 测试操作规则查询
+    #[test]
+    fn action_spec() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetActionSpec {}).unwrap();
+        let value: ActionSpecResponse = from_binary(&res).unwrap();
+        assert_eq!(Action::ALL.len(), value.actions.len());
+
+        // 只有玩家可以在投注阶段押注
+        let guess = value
+            .actions
+            .iter()
+            .find(|spec| spec.action == Action::Guess)
+            .unwrap();
+        assert_eq!(vec![Phase::Betting], guess.phases);
+        assert_eq!(Caller::Player, guess.caller);
+    }
+
+    // 测试单轮模式开奖后不能重置
+    #[test]
+    fn single_round_only() {
+        let mut deps = mock_dependencies(&coins(400, DEFAULT_DENOM));
+
+        let msg = InstantiateMsg {
+            single_round_only: true,
+            ..InstantiateMsg::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 开奖前管理员仍可以重置并提交数字
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reset {};
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        commit_number(&mut deps, 3);
+
+        // 进行一轮游戏
+        let info = mock_info("creator", &coins(200, DEFAULT_DENOM));
+        let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        let info = mock_info("creator", &[]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Start { metadata: None },
+        )
+        .unwrap();
+        let info = mock_info("player", &coins(200, DEFAULT_DENOM));
+        let msg = ExecuteMsg::Guess { is_odd: true };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        reveal_number(&mut deps, 3);
+        let info = mock_info("player", &[]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Lottery {
+                randomness: None,
+                proof: vec![],
+            },
+        )
+        .unwrap();
+
+        // 开奖后重置被拒绝
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reset {};
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::Retired {}) => {}
+            _ => panic!("Must return retired error"),
+        }
+    }
+
+    // 测试延迟发放的奖金在托管期满后才能领取
+    #[test]
+    fn claim_after_payout_delay() {
+        let mut deps = mock_dependencies(&coins(400, DEFAULT_DENOM));
+
+        let msg = InstantiateMsg {
+            payout_delay_seconds: 600,
+            ..InstantiateMsg::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 数字为0，用户押双获胜
+        commit_number(&mut deps, 0);
+        let info = mock_info("creator", &coins(200, DEFAULT_DENOM));
+        let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        let info = mock_info("creator", &[]);
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Start { metadata: None },
+        )
+        .unwrap();
+        let info = mock_info("player", &coins(200, DEFAULT_DENOM));
+        let msg = ExecuteMsg::Guess { is_odd: false };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        reveal_number(&mut deps, 0);
+
+        // 开奖时不直接转账
+        let info = mock_info("player", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Lottery {
+                randomness: None,
+                proof: vec![],
+            },
+        )
+        .unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // 托管期内领取被拒绝
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Claim {});
+        match res {
+            Err(ContractError::CoolingDown {}) => {}
+            _ => panic!("Must return cooling down error"),
+        }
+
+        // 期满后领取全部奖金
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(600);
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::Claim {}).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(400, DEFAULT_DENOM),
+            })
+        );
+
+        // 不能重复领取
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Claim {});
+        match res {
+            Err(ContractError::NothingToClaim {}) => {}
+            _ => panic!("Must return nothing to claim error"),
+        }
+    }
+
+    // 测试两轮游戏后的资金流水
+    #[test]
+    fn flow_stats() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 第一轮：数字为0，用户押双获胜
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "player", 200, false);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFlowStats {}).unwrap();
+        let value: FlowStatsResponse = from_binary(&res).unwrap();
+        assert_eq!(400, value.currently_locked.u128());
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(400, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        run_lottery(&mut deps, "player");
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+
+        // 第二轮：数字为3，用户押双失败
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reset {};
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        commit_number(&mut deps, 3);
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, false);
+        reveal_number(&mut deps, 3);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        run_lottery(&mut deps, "creator");
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFlowStats {}).unwrap();
+        let value: FlowStatsResponse = from_binary(&res).unwrap();
+        assert_eq!(300, value.total_staked_ever.u128());
+        assert_eq!(300, value.total_bonus_ever.u128());
+        assert_eq!(600, value.total_paid_ever.u128());
+        assert_eq!(0, value.currently_locked.u128());
+    }
+
+    // 测试开放投注时把未记账的余额并入奖金
+    #[test]
+    fn sweep_stray_into_bonus() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            sweep_stray_into_bonus: true,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 管理员添加200奖金，合约另外收到50的转账
+        let info = mock_info("creator", &coins(200, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        deps.querier.update_balance(
+            MOCK_CONTRACT_ADDR,
+            vec![coin(250, DEFAULT_DENOM), coin(10, "uusd")],
+        );
+        commit_number(&mut deps, 0);
+
+        let info = mock_info("creator", &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Start { metadata: None },
+        )
+        .unwrap();
+
+        // 只有投注币种的余额并入奖金
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
+        let value: StatusResponse = from_binary(&res).unwrap();
+        assert_eq!(250, value.bonus.u128());
+    }
+
+    // 测试权限查询
+    #[test]
+    fn check_authorization() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let check = |deps: &MockDeps, address: &str, action: &str| -> bool {
+            let msg = QueryMsg::CheckAuthorization {
+                address: address.to_string(),
+                action: action.to_string(),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: AuthorizationResponse = from_binary(&res).unwrap();
+            value.authorized
+        };
+
+        // 准备阶段只有管理员可以添加奖金，提交承诺后才能开放投注
+        assert!(check(&deps, "creator", "add_bonus"));
+        assert!(!check(&deps, "creator", "start"));
+        commit_number(&mut deps, 0);
+        assert!(check(&deps, "creator", "start"));
+        assert!(!check(&deps, "anyone", "add_bonus"));
+        assert!(!check(&deps, "anyone", "reset"));
+        assert!(!check(&deps, "anyone", "guess"));
+
+        // 投注阶段第一个下注的用户成为玩家
+        open_round(&mut deps, 200);
+        assert!(!check(&deps, "creator", "start"));
+        assert!(check(&deps, "anyone", "guess"));
+        place_guess(&mut deps, "player", 200, true);
+        assert!(check(&deps, "player", "guess"));
+        assert!(!check(&deps, "anyone", "guess"));
+
+        // 揭示数字后才能开奖
+        assert!(!check(&deps, "anyone", "lottery"));
+        reveal_number(&mut deps, 0);
+        assert!(check(&deps, "anyone", "lottery"));
+
+        // 未知操作返回错误
+        let msg = QueryMsg::CheckAuthorization {
+            address: "creator".to_string(),
+            action: "unknown".to_string(),
+        };
+        assert!(query(deps.as_ref(), mock_env(), msg).is_err());
+    }
+
+    // 测试对账发现额外转入的资金
+    #[test]
+    fn reconcile() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("creator", &coins(200, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Reconcile {}).unwrap();
+        let value: ReconcileResponse = from_binary(&res).unwrap();
+        assert_eq!("0", value.drift);
+
+        // 有人直接向合约转了30
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(230, DEFAULT_DENOM));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Reconcile {}).unwrap();
+        let value: ReconcileResponse = from_binary(&res).unwrap();
+        assert_eq!(200, value.tracked.u128());
+        assert_eq!(230, value.actual.u128());
+        assert_eq!("30", value.drift);
+    }
+
+    // 测试低于最小发放金额的奖金记入待领取余额
+    #[test]
+    fn dust_payout_becomes_claim() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            min_payout_amount: Uint128::new(100),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 奖金池只有40，用户押双获胜
+        open_round(&mut deps, 20);
+        place_guess(&mut deps, "player", 20, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(40, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "player");
+        assert_eq!(0, res.messages.len());
+
+        // 用户随时可以领取
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Claim {}).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(40, DEFAULT_DENOM),
+            })
+        );
+    }
+
+    // 测试负债汇总待领取奖金和本轮玩家的奖金
+    #[test]
+    fn liabilities() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            payout_delay_seconds: 600,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 第一轮用户获胜，奖金托管中
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "alice", 200, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(400, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        run_lottery(&mut deps, "alice");
+
+        // 第二轮另一个用户下注
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reset {};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "bob", 100, true);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetLiabilities {}).unwrap();
+        let value: LiabilitiesResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![
+                (Addr::unchecked("alice"), Uint128::new(400)),
+                (Addr::unchecked("bob"), Uint128::new(200)),
+            ],
+            value.liabilities
+        );
+    }
+
+    // 测试下注金额的容差
+    #[test]
+    fn match_tolerance() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            match_tolerance: Uint128::new(1),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 200);
+
+        // 差额超出容差时下注没有完成
+        let res = place_guess(&mut deps, "player", 198, true);
+        assert_eq!(attr("remaining", "2"), res.attributes[4]);
+        assert!(!STATE.load(&deps.storage).unwrap().bet_complete);
+
+        // 少付1在容差内视为足额
+        let res = place_guess(&mut deps, "player", 1, true);
+        assert_eq!(0, res.messages.len());
+        assert_eq!(4, res.attributes.len());
+        assert!(STATE.load(&deps.storage).unwrap().bet_complete);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFlowStats {}).unwrap();
+        let value: FlowStatsResponse = from_binary(&res).unwrap();
+        assert_eq!(199, value.total_staked_ever.u128());
+
+        // 再多付2，超过奖金的1退还
+        let res = place_guess(&mut deps, "player", 2, true);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(1, DEFAULT_DENOM),
+            })
+        );
+    }
+
+    // 测试未付清的下注不能占住本轮
+    #[test]
+    fn incomplete_bet_slot() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            bet_duration_seconds: Some(300),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "griefer", 1, true);
+
+        // 付清前管理员不能揭示
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reveal {
+            secret: "0".to_string(),
+            nonce: NONCE.to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::BetIncomplete {}) => {}
+            _ => panic!("Must return bet incomplete error"),
+        }
+
+        // 没有足额下注不能替换
+        let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+        let msg = ExecuteMsg::Guess { is_odd: false };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // 足额下注替换未付清的玩家，退还原玩家已付的金额
+        let res = place_guess(&mut deps, "player", 200, false);
+        assert_eq!(attr("displaced", "griefer"), res.attributes[4]);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "griefer".to_string(),
+                amount: coins(1, DEFAULT_DENOM),
+            })
+        );
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(Some(Addr::unchecked("player")), state.user);
+        assert_eq!(200, state.user_payed.u128());
+        assert!(state.bet_complete);
+
+        // 付清后不能再被替换
+        let info = mock_info("griefer", &coins(200, DEFAULT_DENOM));
+        let msg = ExecuteMsg::Guess { is_odd: true };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // 新的一轮，截止下注时还没有付清
+        let info = mock_info("player", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {}).unwrap();
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "griefer", 1, true);
+
+        // 截止下注前其他人不能取消
+        let info = mock_info("stranger", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {});
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // 截止下注后任何人都可以作废未付清的下注
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(300);
+        let info = mock_info("stranger", &[]);
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::CancelRound {}).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "griefer".to_string(),
+                amount: coins(1, DEFAULT_DENOM),
+            })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(200, DEFAULT_DENOM),
+            })
+        );
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(None, state.user);
+        assert_eq!(Phase::Setup, state.phase());
+    }
+
+    // 测试分两次付清下注
+    #[test]
+    fn partial_payments() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg::default()).unwrap();
+        open_round(&mut deps, 200);
+
+        // 没有附带资金不能下注
+        let info = mock_info("player", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Guess { is_odd: false },
+        );
+        match res {
+            Err(ContractError::Pay {}) => {}
+            _ => panic!("Must return pay error"),
+        }
+
+        // 付清前不能开奖
+        place_guess(&mut deps, "player", 120, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(320, DEFAULT_DENOM));
+        let info = mock_info("player", &[]);
+        let msg = ExecuteMsg::Lottery {
+            randomness: None,
+            proof: vec![],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::BetIncomplete {}) => {}
+            _ => panic!("Must return bet incomplete error"),
+        }
+
+        let res = place_guess(&mut deps, "player", 80, false);
+        assert_eq!(attr("user_payed", "200"), res.attributes[3]);
+        let state = STATE.load(&deps.storage).unwrap();
+        assert!(state.bet_complete);
+        assert_eq!(200, state.user_payed.u128());
+
+        // 数字为0，用户押双获胜
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(400, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "player");
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(400, DEFAULT_DENOM),
+            })
+        );
+    }
+
+    // 测试建议奖金逐轮增长
+    #[test]
+    fn suggested_bonus_grows() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            bonus_growth_bps_per_round: 20_000,
+            ..InstantiateMsg::default()
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg);
+        match res {
+            Err(ContractError::InvalidConfig { .. }) => {}
+            _ => panic!("Must return invalid config error"),
+        }
+        let msg = InstantiateMsg {
+            bonus_growth_bps_per_round: 1_000,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 每轮增长10%
+        let mut expected = vec![];
+        for round in 0..2 {
+            open_round(&mut deps, 1000);
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::GetSuggestedBonus {}).unwrap();
+            let value: SuggestedBonusResponse = from_binary(&res).unwrap();
+            assert_eq!(1000, value.base_bonus.u128());
+            assert_eq!(round + 1, value.rounds_started);
+            expected.push(value.suggested_bonus.u128());
+
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::Reset {};
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+        assert_eq!(vec![1100, 1210], expected);
+    }
+
+    // 测试各种开奖调用者配置
+    #[test]
+    fn lottery_caller_policies() {
+        let cases = vec![
+            (
+                LotteryCaller::Anyone,
+                vec!["anyone", "player", "creator"],
+                vec![],
+            ),
+            (
+                LotteryCaller::PlayerOrOwner,
+                vec!["player", "creator"],
+                vec!["anyone"],
+            ),
+            (
+                LotteryCaller::OwnerOnly,
+                vec!["creator"],
+                vec!["anyone", "player"],
+            ),
+        ];
+        for (lottery_caller, allowed, denied) in cases {
+            for (sender, expect_ok) in allowed
+                .iter()
+                .map(|sender| (sender, true))
+                .chain(denied.iter().map(|sender| (sender, false)))
+            {
+                let mut deps = mock_dependencies(&coins(400, DEFAULT_DENOM));
+                let info = mock_info("creator", &[]);
+                let msg = InstantiateMsg {
+                    lottery_caller,
+                    ..InstantiateMsg::default()
+                };
+                let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+                open_round(&mut deps, 200);
+                place_guess(&mut deps, "player", 200, true);
+                reveal_number(&mut deps, 0);
+
+                let info = mock_info(sender, &[]);
+                let res = execute(
+                    deps.as_mut(),
+                    mock_env(),
+                    info,
+                    ExecuteMsg::Lottery {
+                        randomness: None,
+                        proof: vec![],
+                    },
+                );
+                match res {
+                    Ok(_) if expect_ok => {}
+                    Err(ContractError::Unauthorized {}) if !expect_ok => {}
+                    _ => panic!("Unexpected result for {:?} by {}", lottery_caller, sender),
+                }
+            }
+        }
+    }
+
+    // 测试状态摘要只在状态变化时改变
+    #[test]
+    fn state_digest() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 200);
+
+        let digest = |deps: &MockDeps| -> String {
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStateDigest {}).unwrap();
+            let value: StateDigestResponse = from_binary(&res).unwrap();
+            value.digest
+        };
+        let before = digest(&deps);
+        assert_eq!(64, before.len());
+        assert_eq!(before, digest(&deps));
+
+        place_guess(&mut deps, "player", 200, true);
+        let after = digest(&deps);
+        assert_ne!(before, after);
+        assert_eq!(after, digest(&deps));
+    }
+
+    // 测试开放投注时附带备注信息
+    #[test]
+    fn round_metadata() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            max_metadata_len: Some(8),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("creator", &coins(200, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+
+        // 超过长度限制被拒绝
+        commit_number(&mut deps, 0);
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Start {
+            metadata: Some("summer-promo".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::MetadataTooLong { max: 8 }) => {}
+            _ => panic!("Must return metadata too long error"),
+        }
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Start {
+            metadata: Some("promo-1".to_string()),
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
+        let value: StatusResponse = from_binary(&res).unwrap();
+        assert_eq!(Some("promo-1".to_string()), value.metadata);
+    }
+
+    // 测试查询所有币种余额
+    #[test]
+    fn all_balances() {
+        let balances = vec![coin(300, DEFAULT_DENOM), coin(25, "uusd")];
+        let mut deps = mock_dependencies(&balances);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAllBalances {}).unwrap();
+        let value: AllBalancesResponse = from_binary(&res).unwrap();
+        assert_eq!(balances, value.balances);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetBalance {}).unwrap();
+        let value: BalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(DEFAULT_DENOM, value.denom);
+        assert_eq!(300, value.amount.u128());
+    }
+
+    // 测试添加奖金的最小间隔
+    #[test]
+    fn bonus_interval() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            min_seconds_between_bonus: 60,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("creator", &coins(100, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+
+        // 间隔内再次添加被拒绝
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(59);
+        let info = mock_info("creator", &coins(100, DEFAULT_DENOM));
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::AddBonus {});
+        match res {
+            Err(ContractError::TooFast {}) => {}
+            _ => panic!("Must return too fast error"),
+        }
+
+        // 间隔之后可以添加
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(60);
+        let info = mock_info("creator", &coins(100, DEFAULT_DENOM));
+        execute(deps.as_mut(), env, info, ExecuteMsg::AddBonus {}).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
+        let value: StatusResponse = from_binary(&res).unwrap();
+        assert_eq!(200, value.bonus.u128());
+    }
+
+    // 测试最坏情况下的偿付能力
+    #[test]
+    fn worst_case_solvency() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "player", 200, true);
+
+        let solvency = |deps: &MockDeps| -> SolvencyResponse {
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::IsWorstCaseSolvent {}).unwrap();
+            from_binary(&res).unwrap()
+        };
+
+        // 奖金池全部在合约中
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(400, DEFAULT_DENOM));
+        let value = solvency(&deps);
+        assert!(value.solvent);
+        assert_eq!(0, value.shortfall.u128());
+
+        // 有资金被转走后无法兑付
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(300, DEFAULT_DENOM));
+        let value = solvency(&deps);
+        assert!(!value.solvent);
+        assert_eq!(100, value.shortfall.u128());
+    }
+
+    // 测试多轮游戏后的实际庄家优势
+    #[test]
+    fn realized_edge() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 每轮数字为0，押双获胜、押单失败
+        let rounds = vec![(200, false), (100, true), (300, true)];
+        let mut edges = vec![];
+        for (stake, is_odd) in rounds {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::Reset {};
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+            open_round(&mut deps, stake);
+            place_guess(&mut deps, "player", stake, is_odd);
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(stake * 2, DEFAULT_DENOM));
+            reveal_number(&mut deps, 0);
+            run_lottery(&mut deps, "player");
+            deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+
+            let res = query(deps.as_ref(), mock_env(), QueryMsg::GetRealizedEdge {}).unwrap();
+            let value: RealizedEdgeResponse = from_binary(&res).unwrap();
+            edges.push(value.edge_bps);
+        }
+        // (0 - 200) / 200、(100 - 200) / 300、(400 - 200) / 600
+        assert_eq!(vec!["-10000", "-3333", "3333"], edges);
+    }
+
+    // 测试奖金为0时不能开放投注
+    #[test]
+    fn start_without_bonus() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        commit_number(&mut deps, 0);
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Start { metadata: None };
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+        match res {
+            Err(ContractError::NoBonus {}) => {}
+            _ => panic!("Must return no bonus error"),
+        }
+
+        // 添加奖金后可以开放
+        let info = mock_info("creator", &coins(100, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    // 测试附带多个币种时只统计指定币种
+    #[test]
+    fn mixed_denom_funds() {
+        // 指定币种不在第一位
+        let info = mock_info("player", &[coin(50, "uusd"), coin(100, DEFAULT_DENOM)]);
+        assert_eq!(100, get_coin_u128(&info, DEFAULT_DENOM).u128());
+
+        // 同一币种出现多次时累加
+        let info = mock_info(
+            "player",
+            &[
+                coin(100, DEFAULT_DENOM),
+                coin(50, "uusd"),
+                coin(30, DEFAULT_DENOM),
+            ],
+        );
+        assert_eq!(130, get_coin_u128(&info, DEFAULT_DENOM).u128());
+
+        let info = mock_info("player", &[coin(50, "uusd")]);
+        assert_eq!(Uint128::zero(), get_coin_u128(&info, DEFAULT_DENOM));
+    }
+
+    // 测试使用自定义币种
+    #[test]
+    fn custom_denom() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            denom: Some("uusd".to_string()),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 其他币种不计入奖金
+        let info = mock_info("creator", &coins(100, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        let info = mock_info("creator", &coins(200, "uusd"));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
+        let value: StatusResponse = from_binary(&res).unwrap();
+        assert_eq!(200, value.bonus.u128());
+    }
+
+    // 测试查询完整状态，开奖前不返回数字
+    #[test]
+    fn full_state_hides_number() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reset {};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        commit_number(&mut deps, 3);
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, true);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetState {}).unwrap();
+        let value: FullStateResponse = from_binary(&res).unwrap();
+        assert_eq!(None, value.guess_number);
+        assert_eq!(Some(Addr::unchecked("player")), value.user);
+        assert_eq!(100, value.user_payed.u128());
+        assert!(value.guess_is_odd);
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        reveal_number(&mut deps, 3);
+        run_lottery(&mut deps, "player");
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetState {}).unwrap();
+        let value: FullStateResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(3), value.guess_number);
+        assert!(value.is_lottery);
+        assert_eq!(Addr::unchecked("creator"), value.owner);
+    }
+
+    // 测试余额不足时自动暂停押注
+    #[test]
+    fn pause_on_insolvency() {
+        let mut deps = mock_dependencies(&coins(100, DEFAULT_DENOM));
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            pause_on_insolvency: true,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+
+        // 奖金被转走一半，玩家下注后余额仍不足
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(150, DEFAULT_DENOM));
+        let res = place_guess(&mut deps, "player", 100, true);
+        assert_eq!("insolvency_detected", res.events[0].ty);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(100, DEFAULT_DENOM),
+            })
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPauseReason {}).unwrap();
+        let value: PauseReasonResponse = from_binary(&res).unwrap();
+        assert!(value.paused);
+        assert!(value.reason.is_some());
+
+        let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+        let msg = ExecuteMsg::Guess { is_odd: true };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::Paused {}) => {}
+            _ => panic!("Must return paused error"),
+        }
+
+        // 补足余额前不能恢复
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(50, DEFAULT_DENOM));
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Resume {});
+        match res {
+            Err(ContractError::Insolvent {}) => {}
+            _ => panic!("Must return insolvent error"),
+        }
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(100, DEFAULT_DENOM));
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Resume {}).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPauseReason {}).unwrap();
+        let value: PauseReasonResponse = from_binary(&res).unwrap();
+        assert!(!value.paused);
+    }
+
+    // 测试补充资金后解除暂停
+    #[test]
+    fn top_up_clears_pause() {
+        let mut deps = mock_dependencies(&coins(100, DEFAULT_DENOM));
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            pause_on_insolvency: true,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(150, DEFAULT_DENOM));
+        place_guess(&mut deps, "player", 100, true);
+
+        // 补充后余额仍不足，保持暂停
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(80, DEFAULT_DENOM));
+        let info = mock_info("creator", &coins(30, DEFAULT_DENOM));
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::TopUp {}).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "top_up"),
+                attr("amount", "30"),
+                attr("solvent", "false"),
+                attr("paused", "true"),
+            ]
+        );
+
+        // 补足后解除暂停
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(120, DEFAULT_DENOM));
+        let info = mock_info("creator", &coins(40, DEFAULT_DENOM));
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::TopUp {}).unwrap();
+        assert_eq!(attr("solvent", "true"), res.attributes[2]);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPauseReason {}).unwrap();
+        let value: PauseReasonResponse = from_binary(&res).unwrap();
+        assert!(!value.paused);
+    }
+
+    // 测试备用金不参与开奖分配，也不会被当作零头提取
+    #[test]
+    fn top_up_reserve() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg::default()).unwrap();
+
+        let info = mock_info("creator", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::WithdrawReserve {},
+        );
+        match res {
+            Err(ContractError::NoReserve {}) => {}
+            _ => panic!("Must return no reserve error"),
+        }
+
+        let info = mock_info("creator", &coins(500, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::TopUp {}).unwrap();
+        assert_eq!(500, STATE.load(&deps.storage).unwrap().reserve.u128());
+
+        // 数字为0，用户押双获胜，只获得奖金和下注
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(700, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "player");
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(200, DEFAULT_DENOM),
+            })
+        );
+
+        // 备用金不是零头
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(500, DEFAULT_DENOM));
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CollectDust {}).unwrap();
+        assert_eq!(0, res.messages.len());
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Reconcile {}).unwrap();
+        let value: ReconcileResponse = from_binary(&res).unwrap();
+        assert_eq!(500, value.tracked.u128());
+
+        // 只有管理员可以提取备用金
+        let info = mock_info("player", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::WithdrawReserve {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let info = mock_info("creator", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::WithdrawReserve {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(500, DEFAULT_DENOM),
+            })
+        );
+        assert!(STATE.load(&deps.storage).unwrap().reserve.is_zero());
+    }
+
+    // 测试执行操作返回的属性
+    #[test]
+    fn handler_attributes() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("creator", &coins(100, DEFAULT_DENOM));
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "add_bonus"),
+                attr("amount", "100"),
+                attr("bonus", "100"),
+            ]
+        );
+        commit_number(&mut deps, 0);
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Start { metadata: None };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = place_guess(&mut deps, "player", 100, false);
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "guess"),
+                attr("user", "player"),
+                attr("guess_is_odd", "false"),
+                attr("user_payed", "100"),
+            ]
+        );
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "player");
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "lottery"),
+                attr("winner", "player"),
+                attr("payout", "200"),
+                attr("commission", "0"),
+            ]
+        );
+    }
+
+    // 测试用户获胜时庄家抽取佣金
+    #[test]
+    fn commission_on_win() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            commission_bps: 500,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        open_round(&mut deps, 1000);
+        place_guess(&mut deps, "player", 1000, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(2000, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "player");
+
+        // 佣金为奖金的 5%，留在合约中
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(1950, DEFAULT_DENOM),
+            })],
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>()
+        );
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(50, state.accrued_fees.u128());
+    }
+
+    // 测试佣金比例超过 10000 时初始化失败
+    #[test]
+    fn commission_too_high() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            commission_bps: 10_001,
+            ..InstantiateMsg::default()
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::InvalidConfig { field }) => assert_eq!("commission_bps", field),
+            _ => panic!("Must return invalid config error"),
+        }
+    }
+
+    // 测试赢家按本金的倍数获奖
+    #[test]
+    fn win_multiplier() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            win_multiplier_bps: 18_000,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        open_round(&mut deps, 1000);
+        place_guess(&mut deps, "player", 1000, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(2000, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "player");
+
+        // 用户获得本金的 1.8 倍，剩下的转给管理员
+        assert_eq!(attr("house_share", "200"), res.attributes[4]);
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "player".to_string(),
+                    amount: coins(1800, DEFAULT_DENOM),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "creator".to_string(),
+                    amount: coins(200, DEFAULT_DENOM),
+                }),
+            ],
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>()
+        );
+
+        // 奖金池不足以支付的倍数初始化失败
+        for msg in [
+            InstantiateMsg {
+                win_multiplier_bps: 20_001,
+                ..InstantiateMsg::default()
+            },
+            InstantiateMsg {
+                win_multiplier_bps: 9_999,
+                ..InstantiateMsg::default()
+            },
+            InstantiateMsg {
+                win_multiplier_bps: 18_000,
+                multi_player: true,
+                ..InstantiateMsg::default()
+            },
+        ] {
+            let mut deps = mock_dependencies(&[]);
+            let info = mock_info("creator", &[]);
+            match instantiate(deps.as_mut(), mock_env(), info, msg) {
+                Err(ContractError::InvalidConfig { field }) => {
+                    assert_eq!("win_multiplier_bps", field)
+                }
+                _ => panic!("Must return invalid config error"),
+            }
+        }
+    }
+
+    // 测试达到每日亏损上限后不能下注，第二天恢复
+    #[test]
+    fn daily_loss_limit() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            daily_loss_limit: Some(Uint128::new(200)),
+            reveal_timeout_seconds: Some(2 * 86_400),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 数字为0，押单连输两轮
+        for _ in 0..2 {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::Reset {};
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+            open_round(&mut deps, 100);
+            place_guess(&mut deps, "player", 100, true);
+            reveal_number(&mut deps, 0);
+            run_lottery(&mut deps, "player");
+        }
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reset {};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+        let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+        let msg = ExecuteMsg::Guess { is_odd: true };
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+        match res {
+            Err(ContractError::LossLimitReached {}) => {}
+            _ => panic!("Must return loss limit reached error"),
+        }
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(86_400);
+        let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+        execute(deps.as_mut(), env, info, msg).unwrap();
+    }
+
+    // 测试提交承诺和揭示数字
+    #[test]
+    fn commit_reveal() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 没有承诺不能开放投注
+        let info = mock_info("creator", &coins(100, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Start { metadata: None };
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+        match res {
+            Err(ContractError::NoCommitment {}) => {}
+            _ => panic!("Must return no commitment error"),
+        }
+        commit_number(&mut deps, 7);
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        place_guess(&mut deps, "player", 100, true);
+
+        // 揭示前不能开奖
+        let info = mock_info("player", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Lottery {
+                randomness: None,
+                proof: vec![],
+            },
+        );
+        match res {
+            Err(ContractError::NotRevealed {}) => {}
+            _ => panic!("Must return not revealed error"),
+        }
+
+        // 揭示的内容必须和承诺一致
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reveal {
+            secret: "8".to_string(),
+            nonce: NONCE.to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::HashMismatch {}) => {}
+            _ => panic!("Must return hash mismatch error"),
+        }
+        reveal_number(&mut deps, 7);
+
+        // 揭示后不能再修改下注
+        let info = mock_info("player", &[]);
+        let msg = ExecuteMsg::Guess { is_odd: false };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::AlreadyRevealed {}) => {}
+            _ => panic!("Must return already revealed error"),
+        }
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        let res = run_lottery(&mut deps, "player");
+        assert_eq!(attr("winner", "player"), res.attributes[1]);
+    }
+
+    // 测试同一个承诺不能揭示为不同的数字
+    #[test]
+    fn reveal_is_unambiguous() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 承诺数字1，揭示时把 nonce 的第一个字符移到 secret 中
+        let hash = commitment_hash("1", "2abc");
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Commit { hash }).unwrap();
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, true);
+        for (secret, nonce) in [("12", "abc"), ("1:2", "abc")] {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::Reveal {
+                secret: secret.to_string(),
+                nonce: nonce.to_string(),
+            };
+            let res = execute(deps.as_mut(), mock_env(), info, msg);
+            match res {
+                Err(ContractError::HashMismatch {}) => {}
+                _ => panic!("Must return hash mismatch error"),
+            }
+        }
+
+        // 不是规范写法的数字被拒绝
+        for secret in ["+5", "05", "-0"] {
+            let mut deps = mock_dependencies(&[]);
+            let info = mock_info("creator", &[]);
+            let msg = InstantiateMsg::default();
+            let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+            let hash = commitment_hash(secret, NONCE);
+            let info = mock_info("creator", &[]);
+            execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Commit { hash }).unwrap();
+            open_round(&mut deps, 100);
+            place_guess(&mut deps, "player", 100, true);
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::Reveal {
+                secret: secret.to_string(),
+                nonce: NONCE.to_string(),
+            };
+            let res = execute(deps.as_mut(), mock_env(), info, msg);
+            match res {
+                Err(ContractError::InvalidSecret {}) => {}
+                _ => panic!("Must return invalid secret error"),
+            }
+        }
+    }
+
+    // 测试用信标的随机数开奖
+    #[test]
+    fn beacon_randomness() {
+        // 两个叶子的默克尔树，第一轮使用 randomness
+        let randomness = Binary::from(b"round-1-randomness".to_vec());
+        let leaf = beacon::leaf(1, &randomness);
+        let sibling = beacon::leaf(2, b"round-2-randomness");
+        let (left, right) = if leaf <= sibling {
+            (&leaf, &sibling)
+        } else {
+            (&sibling, &leaf)
+        };
+        let root = Sha256::new().chain(left).chain(right).finalize().to_vec();
+
+        // 必须配置截止下注的时间
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let mut msg = InstantiateMsg {
+            beacon_root: Some(Binary::from(root)),
+            min_number: Some(1),
+            max_number: Some(6),
+            ..InstantiateMsg::default()
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg.clone());
+        match res {
+            Err(ContractError::InvalidConfig { field }) => {
+                assert_eq!("bet_duration_seconds", field)
+            }
+            _ => panic!("Must return invalid config error"),
+        }
+        msg.bet_duration_seconds = Some(300);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 不需要也不能提交承诺
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Commit {
+            hash: commitment_hash("1", NONCE),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::BeaconConfigured {}) => {}
+            _ => panic!("Must return beacon configured error"),
+        }
+        let info = mock_info("creator", &coins(100, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Start { metadata: None };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        place_guess(&mut deps, "player", 100, true);
+
+        // 管理员不能揭示数字
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reveal {
+            secret: "1".to_string(),
+            nonce: NONCE.to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::BeaconConfigured {}) => {}
+            _ => panic!("Must return beacon configured error"),
+        }
+
+        // 截止下注前不能开奖
+        let msg = ExecuteMsg::Lottery {
+            randomness: Some(randomness.clone()),
+            proof: vec![Binary::from(sibling.clone())],
+        };
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+        match res {
+            Err(ContractError::BettingOpen {}) => {}
+            _ => panic!("Must return betting open error"),
+        }
+
+        // 截止下注后玩家不能再改押或者取消
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(300);
+        let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+        let guess = ExecuteMsg::Guess { is_odd: false };
+        let res = execute(deps.as_mut(), env.clone(), info, guess);
+        match res {
+            Err(ContractError::BettingClosed {}) => {}
+            _ => panic!("Must return betting closed error"),
+        }
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::CancelRound {});
+        match res {
+            Err(ContractError::BettingClosed {}) => {}
+            _ => panic!("Must return betting closed error"),
+        }
+
+        // 篡改随机数后证明无效
+        let info = mock_info("player", &[]);
+        let tampered = ExecuteMsg::Lottery {
+            randomness: Some(Binary::from(b"round-1-tampered".to_vec())),
+            proof: vec![Binary::from(sibling)],
+        };
+        let res = execute(deps.as_mut(), env.clone(), info, tampered);
+        match res {
+            Err(ContractError::BadProof {}) => {}
+            _ => panic!("Must return bad proof error"),
+        }
+
+        let info = mock_info("player", &[]);
+        execute(deps.as_mut(), env, info, msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetState {}).unwrap();
+        let value: FullStateResponse = from_binary(&res).unwrap();
+        let number = beacon::draw(&randomness, 1, 6);
+        assert!((1..=6).contains(&number));
+        assert_eq!(Some(number), value.guess_number);
+    }
+
+    // 测试两步交接管理员
+    #[test]
+    fn ownership_transfer() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 只有管理员可以提名
+        let msg = ExecuteMsg::TransferOwnership {
+            new_owner: "successor".to_string(),
+        };
+        let info = mock_info("anyone", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 只有被提名的地址可以接受
+        let info = mock_info("anyone", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::AcceptOwnership {},
+        );
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // 接受前仍由原管理员管理
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetState {}).unwrap();
+        let value: FullStateResponse = from_binary(&res).unwrap();
+        assert_eq!(Addr::unchecked("creator"), value.owner);
+
+        let info = mock_info("successor", &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::AcceptOwnership {},
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetState {}).unwrap();
+        let value: FullStateResponse = from_binary(&res).unwrap();
+        assert_eq!(Addr::unchecked("successor"), value.owner);
+
+        // 原管理员失去权限
+        let info = mock_info("creator", &coins(100, DEFAULT_DENOM));
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {});
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+    }
+
+    // 测试本轮的实时统计
+    #[test]
+    fn round_metrics() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, true);
+
+        let metrics = |deps: &MockDeps, seconds: u64| -> RoundMetricsResponse {
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(seconds);
+            let res = query(deps.as_ref(), env, QueryMsg::GetRoundMetrics {}).unwrap();
+            from_binary(&res).unwrap()
+        };
+
+        let value = metrics(&deps, 30);
+        assert_eq!(30, value.elapsed_seconds);
+        assert_eq!(1, value.bets_placed);
+        assert_eq!(100, value.total_staked.u128());
+        assert_eq!(Decimal::from_ratio(2u128, 1u128), value.bets_per_minute);
+
+        let value = metrics(&deps, 240);
+        assert_eq!(240, value.elapsed_seconds);
+        assert_eq!(Decimal::from_ratio(1u128, 4u128), value.bets_per_minute);
+    }
+
+    // 测试多人模式下猜对的玩家按下注比例瓜分奖金池
+    #[test]
+    fn multi_player_round() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            multi_player: true,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 数字为0，alice 和 bob 押双，carol 押单
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "alice", 100, false);
+        place_guess(&mut deps, "bob", 100, false);
+        place_guess(&mut deps, "carol", 100, true);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetLiabilities {}).unwrap();
+        let value: LiabilitiesResponse = from_binary(&res).unwrap();
+        let total = value
+            .liabilities
+            .iter()
+            .fold(Uint128::new(0), |total, (_, amount)| total + *amount);
+        assert_eq!(400, total.u128());
+
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(400, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "anyone");
+        assert_eq!(attr("winners", "2"), res.attributes[1]);
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(200, DEFAULT_DENOM),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(200, DEFAULT_DENOM),
+                }),
+            ],
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>()
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFlowStats {}).unwrap();
+        let value: FlowStatsResponse = from_binary(&res).unwrap();
+        assert_eq!(300, value.total_staked_ever.u128());
+    }
+
+    // 测试多人模式下已下注的玩家可以在揭示前取消本轮
+    #[test]
+    fn multi_player_cancel() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            multi_player: true,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "alice", 100, false);
+        place_guess(&mut deps, "bob", 100, true);
+
+        // 没有下注的人和管理员不能取消
+        for sender in ["stranger", "creator"] {
+            let info = mock_info(sender, &[]);
+            let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {});
+            match res {
+                Err(ContractError::Unauthorized {}) => {}
+                _ => panic!("Must return unauthorized error"),
+            }
+        }
+
+        let info = mock_info("bob", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {}).unwrap();
+        assert_eq!(attr("refunded", "200"), res.attributes[1]);
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(100, DEFAULT_DENOM),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(100, DEFAULT_DENOM),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "creator".to_string(),
+                    amount: coins(100, DEFAULT_DENOM),
+                }),
+            ],
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>()
+        );
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(Phase::Setup, state.phase());
+        assert!(!BETS.has(&deps.storage, &Addr::unchecked("alice")));
+    }
+
+    // 测试没有截止时间时揭示超时从开放投注时开始计算
+    #[test]
+    fn reveal_timeout_without_deadline() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            multi_player: true,
+            lottery_caller: LotteryCaller::OwnerOnly,
+            reveal_timeout_seconds: Some(600),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 数字为1，alice 和 bob 押双，管理员不揭示
+        commit_number(&mut deps, 1);
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "alice", 100, false);
+        place_guess(&mut deps, "bob", 100, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(300, DEFAULT_DENOM));
+
+        let msg = ExecuteMsg::Lottery {
+            randomness: None,
+            proof: vec![],
+        };
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(599);
+        let info = mock_info("stranger", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg.clone());
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // 超时后不能再下注，任何人都可以开奖
+        env.block.time = env.block.time.plus_seconds(1);
+        let info = mock_info("carol", &coins(100, DEFAULT_DENOM));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::Guess { is_odd: true },
+        );
+        match res {
+            Err(ContractError::BettingClosed {}) => {}
+            _ => panic!("Must return betting closed error"),
+        }
+        let info = mock_info("stranger", &[]);
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        assert_eq!(attr("winners", "2"), res.attributes[1]);
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(150, DEFAULT_DENOM),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(150, DEFAULT_DENOM),
+                }),
+            ],
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    // 测试玩家的前几轮免收佣金
+    #[test]
+    fn free_rounds_waive_commission() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            commission_bps: 500,
+            free_rounds: 1,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 第一轮免收佣金，第二轮收取 5%
+        let mut commissions = vec![];
+        for _ in 0..2 {
+            let info = mock_info("creator", &[]);
+            execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+            open_round(&mut deps, 1000);
+            place_guess(&mut deps, "player", 1000, false);
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(2000, DEFAULT_DENOM));
+            reveal_number(&mut deps, 0);
+            let res = run_lottery(&mut deps, "player");
+            commissions.push(res.attributes[3].value.clone());
+            // 奖金已经转给玩家
+            deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+        }
+        assert_eq!(vec!["0", "50"], commissions);
+    }
+
+    // 测试多付的下注金额退还
+    #[test]
+    fn refund_overpayment() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 200);
+
+        let res = place_guess(&mut deps, "player", 250, true);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(50, DEFAULT_DENOM),
+            })
+        );
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetState {}).unwrap();
+        let value: FullStateResponse = from_binary(&res).unwrap();
+        assert_eq!(200, value.user_payed.u128());
+    }
+
+    // 测试按查询到的格式计算的承诺和合约一致
+    #[test]
+    fn commitment_preimage_format() {
+        let deps = mock_dependencies(&[]);
+        let msg = QueryMsg::GetCommitmentPreimageFormat {};
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let format: CommitmentFormatResponse = from_binary(&res).unwrap();
+        assert_eq!(vec!["secret", "nonce"], format.fields);
+        assert_eq!("sha256", format.hash);
+        assert_eq!("utf8", format.encoding);
+        assert_eq!("lowercase hex", format.output);
+
+        let preimage = ["42", "salt"].join(&format.separator);
+        let expected = hex::encode(Sha256::digest(preimage.as_bytes()));
+        assert_eq!(expected, commitment_hash("42", "salt"));
+        assert_eq!(":", format.separator);
+        assert_eq!(
+            "cecb21329daea6121e0471c52be8ea1d59028c2daf2efff4a59fefe03581c229",
+            expected
+        );
+    }
+
+    // 测试开奖记录的保存和分页查询
+    #[test]
+    fn round_history() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 每轮数字为0，押双获胜、押单失败
+        for (stake, is_odd) in [(200, false), (100, true), (300, false)] {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::Reset {};
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+            commit_number(&mut deps, 0);
+            let info = mock_info("creator", &coins(stake, DEFAULT_DENOM));
+            execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::Start {
+                metadata: Some(format!("stake {}", stake)),
+            };
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+            place_guess(&mut deps, "player", stake, is_odd);
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(stake * 2, DEFAULT_DENOM));
+            reveal_number(&mut deps, 0);
+            run_lottery(&mut deps, "player");
+            deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+        }
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetRound { id: 2 }).unwrap();
+        let value: RoundRecord = from_binary(&res).unwrap();
+        assert_eq!(
+            RoundRecord {
+                guess_number: 0,
+                guess_is_odd: true,
+                user: Some(Addr::unchecked("player")),
+                bonus: Uint128::new(100),
+                winner: Some(Addr::unchecked("creator")),
+                metadata: Some("stake 100".to_string()),
+            },
+            value
+        );
+
+        let msg = QueryMsg::ListRounds {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: RoundsResponse = from_binary(&res).unwrap();
+        let ids: Vec<u64> = value.rounds.iter().map(|(id, _)| *id).collect();
+        assert_eq!(vec![1, 2], ids);
+
+        let msg = QueryMsg::ListRounds {
+            start_after: Some(2),
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: RoundsResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.rounds.len());
+        assert_eq!(3, value.rounds[0].0);
+        assert_eq!(Some(Addr::unchecked("player")), value.rounds[0].1.winner);
+        assert_eq!(Some("stake 300".to_string()), value.rounds[0].1.metadata);
+
+        // 不存在的记录
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetRound { id: 4 });
+        assert!(res.is_err());
+    }
+
+    // 测试紧凑编码的状态和 GetStatus 一致
+    #[test]
+    fn status_compact() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 300);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatusCompact {}).unwrap();
+        let compact: Binary = from_binary(&res).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
+        let status: StatusResponse = from_binary(&res).unwrap();
+
+        assert_eq!(25, compact.len());
+        assert_eq!(status.playing, compact[0] == 1);
+        let bonus = u128::from_be_bytes(compact[1..17].try_into().unwrap());
+        assert_eq!(status.bonus.u128(), bonus);
+        let deadline = u64::from_be_bytes(compact[17..25].try_into().unwrap());
+        assert_eq!(0, deadline);
+    }
+
+    // 测试本轮数字的范围
+    #[test]
+    fn number_range() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            min_number: Some(10),
+            max_number: Some(5),
+            ..InstantiateMsg::default()
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg);
+        match res {
+            Err(ContractError::InvalidConfig { .. }) => {}
+            _ => panic!("Must return invalid config error"),
+        }
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 默认范围为 0 到 100
+        commit_number(&mut deps, -5);
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, true);
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Reveal {
+            secret: "-5".to_string(),
+            nonce: NONCE.to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::InvalidNumber { num: -5 }) => {}
+            _ => panic!("Must return invalid number error"),
+        }
+
+        // 范围内的数字可以揭示
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        commit_number(&mut deps, 100);
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, false);
+        reveal_number(&mut deps, 100);
+    }
+
+    // 测试赢家领取奖金前不能重置
+    #[test]
+    fn reset_blocked_until_claimed() {
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            payout_delay_seconds: 600,
+            block_reset_until_claimed: true,
+            ..InstantiateMsg::default()
+        };
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 数字为0，用户押双获胜，奖金托管在合约中
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "player", 200, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(400, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        run_lottery(&mut deps, "player");
+
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {});
+        match res {
+            Err(ContractError::UnclaimedWinnings {}) => {}
+            _ => panic!("Must return unclaimed winnings error"),
+        }
+
+        // 领取后可以重置
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(600);
+        let info = mock_info("player", &[]);
+        execute(deps.as_mut(), env, info, ExecuteMsg::Claim {}).unwrap();
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+    }
+
+    // 测试重置不转出资金，零头单独提取
+    #[test]
+    fn reset_and_collect_dust() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            commission_bps: 500,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 数字为0，用户押双获胜，扣除佣金10后直接转给用户
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "player", 200, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(400, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "player");
+        assert_eq!(1, res.messages.len());
+
+        // 奖金转出后有人向合约转账，重置不受影响
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(410, DEFAULT_DENOM));
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // 只有管理员可以提取零头
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CollectDust {});
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
 
-    // 测试初始化
+        // 佣金留在合约中，其余的零头转给管理员
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CollectDust {}).unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(400, DEFAULT_DENOM),
+            })],
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    // 测试取消本轮时退还玩家的下注
     #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies(&coins(1000, DENOM));
+    fn cancel_round() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        let msg = InstantiateMsg {};
-        let info = mock_info("creator", &coins(1000, DENOM));
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "player", 200, false);
 
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+        // 玩家下注后管理员不能取消，其他人也不能取消
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {});
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let info = mock_info("stranger", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {});
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
 
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
-        let value: StatusResponse = from_binary(&res).unwrap();
-        assert_eq!(0, value.bonus.u128());
+        // 玩家在揭示前同意取消
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {}).unwrap();
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "player".to_string(),
+                    amount: coins(200, DEFAULT_DENOM),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "creator".to_string(),
+                    amount: coins(200, DEFAULT_DENOM),
+                }),
+            ],
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>()
+        );
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(Phase::Setup, state.phase());
+        assert_eq!(None, state.user);
+        assert_eq!(Uint128::zero(), state.bonus);
+
+        // 开奖后不能取消
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "player", 200, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(400, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        run_lottery(&mut deps, "player");
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {});
+        match res {
+            Err(ContractError::RoundSettled {}) => {}
+            _ => panic!("Must return round settled error"),
+        }
     }
 
+    // 测试揭示对庄家不利的数字后不能取消
     #[test]
-    fn set_bonus() {
-        let mut deps = mock_dependencies(&coins(1000, DENOM));
+    fn cancel_after_losing_reveal() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // 初始化合约
-        let msg = InstantiateMsg {};
-        let info = mock_info("creator", &coins(200, DENOM));
+        // 还没有玩家时管理员可以取消，奖金退还给管理员
+        open_round(&mut deps, 200);
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {}).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(200, DEFAULT_DENOM),
+            })
+        );
+
+        // 数字为0，用户押双获胜
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "player", 200, false);
+        reveal_number(&mut deps, 0);
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {});
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // 揭示后玩家也不能取消
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelRound {});
+        match res {
+            Err(ContractError::AlreadyRevealed {}) => {}
+            _ => panic!("Must return already revealed error"),
+        }
+    }
+
+    // 测试不接受转账的操作附带资金
+    #[test]
+    fn reject_unexpected_funds() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
         let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        commit_number(&mut deps, 0);
+        let info = mock_info("creator", &coins(100, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
 
-        // 管理员设置奖金为200
-        let info = mock_info("creator", &coins(200, DENOM));
-        let msg = ExecuteMsg::AddBonus {};
-        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let info = mock_info("creator", &coins(5, DEFAULT_DENOM));
+        let msg = ExecuteMsg::Start { metadata: None };
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+        match res {
+            Err(ContractError::UnexpectedFunds {}) => {}
+            _ => panic!("Must return unexpected funds error"),
+        }
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // 查询奖金是否200
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
-        let value: StatusResponse = from_binary(&res).unwrap();
-        assert_eq!(200, value.bonus.u128());
+        place_guess(&mut deps, "player", 100, false);
+        reveal_number(&mut deps, 0);
+        let info = mock_info("player", &coins(5, DEFAULT_DENOM));
+        let msg = ExecuteMsg::Lottery {
+            randomness: None,
+            proof: vec![],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::UnexpectedFunds {}) => {}
+            _ => panic!("Must return unexpected funds error"),
+        }
+
+        let info = mock_info("creator", &coins(5, DEFAULT_DENOM));
+        let msg = ExecuteMsg::TransferOwnership {
+            new_owner: "next".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::UnexpectedFunds {}) => {}
+            _ => panic!("Must return unexpected funds error"),
+        }
     }
 
-    // 测试重置合约
+    // 测试历史开奖数字的单双分布
     #[test]
-    fn reset() {
-        let mut deps = mock_dependencies(&coins(2000, DENOM));
+    fn draw_histogram() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // 初始化合约
-        let msg = InstantiateMsg {};
-        let info = mock_info("creator", &coins(2, DENOM));
+        for num in [3, 8, 5, 7] {
+            let info = mock_info("creator", &[]);
+            execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+            commit_number(&mut deps, num);
+            open_round(&mut deps, 100);
+            place_guess(&mut deps, "player", 100, true);
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+            reveal_number(&mut deps, num);
+            run_lottery(&mut deps, "player");
+            deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+        }
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetDrawHistogram {}).unwrap();
+        let value: DrawHistogram = from_binary(&res).unwrap();
+        assert_eq!(DrawHistogram { odd: 3, even: 1 }, value);
+    }
+
+    // 测试代他人下注
+    #[test]
+    fn guess_for_beneficiary() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
         let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
 
-        // 设置奖金为200
-        let info = mock_info("creator", &coins(200, DENOM));
-        let msg = ExecuteMsg::AddBonus {};
-        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        // 没有开启时不能代下注
+        let msg = ExecuteMsg::GuessFor {
+            beneficiary: "friend".to_string(),
+            is_odd: false,
+        };
+        let info = mock_info("funder", &coins(100, DEFAULT_DENOM));
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+        match res {
+            Err(ContractError::GuessForDisabled {}) => {}
+            _ => panic!("Must return guess for disabled error"),
+        }
 
-        // 测试奖金是否200
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let init = InstantiateMsg {
+            allow_guess_for: true,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, init).unwrap();
+        open_round(&mut deps, 100);
+
+        // 多付的部分退还给调用者
+        let info = mock_info("funder", &coins(150, DEFAULT_DENOM));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "funder".to_string(),
+                amount: coins(50, DEFAULT_DENOM),
+            })
+        );
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(Some(Addr::unchecked("friend")), state.user);
+
+        // 已经有玩家时不能再代下注
+        let msg = ExecuteMsg::GuessFor {
+            beneficiary: "friend".to_string(),
+            is_odd: true,
+        };
+        let info = mock_info("other", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+
+        // 数字为0，受益人押双获胜
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "friend");
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "friend".to_string(),
+                amount: coins(200, DEFAULT_DENOM),
+            })
+        );
+        let stats = USER_STATS
+            .load(&deps.storage, &Addr::unchecked("friend"))
+            .unwrap();
+        assert_eq!(1, stats.games_played);
+
+        // 代付的一部分被足额下注替换时退还给受益人
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+        open_round(&mut deps, 100);
+        let msg = ExecuteMsg::GuessFor {
+            beneficiary: "friend".to_string(),
+            is_odd: true,
+        };
+        let info = mock_info("funder", &coins(50, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let res = place_guess(&mut deps, "player", 100, false);
+        assert_eq!(attr("displaced", "friend"), res.attributes[4]);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "friend".to_string(),
+                amount: coins(50, DEFAULT_DENOM),
+            })
+        );
+    }
+
+    // 测试地址的累计统计
+    #[test]
+    fn user_stats() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg::default()).unwrap();
+
+        // 第一轮数字为0，押双获胜；第二轮数字为1，押双失败
+        for number in [0, 1] {
+            let info = mock_info("creator", &[]);
+            execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+            commit_number(&mut deps, number);
+            open_round(&mut deps, 200);
+            place_guess(&mut deps, "player", 200, false);
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(400, DEFAULT_DENOM));
+            reveal_number(&mut deps, number);
+            run_lottery(&mut deps, "player");
+            deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+        }
+
+        let msg = QueryMsg::GetUserStats {
+            address: "player".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: UserStats = from_binary(&res).unwrap();
+        assert_eq!(
+            UserStats {
+                games_played: 2,
+                games_won: 1,
+                total_wagered: Uint128::new(400),
+                total_won: Uint128::new(400),
+            },
+            value
+        );
+
+        // 没有参与过的地址返回默认值
+        let msg = QueryMsg::GetUserStats {
+            address: "stranger".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: UserStats = from_binary(&res).unwrap();
+        assert_eq!(UserStats::default(), value);
+    }
+
+    // 测试猜数字玩法
+    #[test]
+    fn exact_guess_mode() {
+        // 猜单双玩法下不能猜数字
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg::default()).unwrap();
+        open_round(&mut deps, 100);
+        let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::GuessExact { number: 7 },
+        );
+        match res {
+            Err(ContractError::WrongGuessMode {}) => {}
+            _ => panic!("Must return wrong guess mode error"),
+        }
+
+        // 猜数字不支持多人模式
+        let msg = InstantiateMsg {
+            guess_mode: GuessMode::Exact,
+            min_number: Some(0),
+            max_number: Some(9),
+            ..InstantiateMsg::default()
+        };
+        let init = InstantiateMsg {
+            multi_player: true,
+            ..msg.clone()
+        };
+        let info = mock_info("creator", &[]);
+        let res = instantiate(deps.as_mut(), mock_env(), info, init);
+        match res {
+            Err(ContractError::InvalidConfig { .. }) => {}
+            _ => panic!("Must return invalid config error"),
+        }
+
+        // 数字为7，猜3和本轮数字同为单数也算猜错
+        for (guessed, winner) in [(3, "creator"), (7, "player")] {
+            let mut deps = mock_dependencies(&[]);
+            let info = mock_info("creator", &[]);
+            let _res = instantiate(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+            commit_number(&mut deps, 7);
+            open_round(&mut deps, 100);
+
+            let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                info,
+                ExecuteMsg::Guess { is_odd: true },
+            );
+            match res {
+                Err(ContractError::WrongGuessMode {}) => {}
+                _ => panic!("Must return wrong guess mode error"),
+            }
+            // 超出数字范围不能下注
+            let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+            let msg = ExecuteMsg::GuessExact { number: 10 };
+            let res = execute(deps.as_mut(), mock_env(), info, msg);
+            match res {
+                Err(ContractError::InvalidNumber { num: 10 }) => {}
+                _ => panic!("Must return invalid number error"),
+            }
+            let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+            let msg = ExecuteMsg::GuessExact { number: guessed };
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+            reveal_number(&mut deps, 7);
+            let res = run_lottery(&mut deps, "player");
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: winner.to_string(),
+                    amount: coins(200, DEFAULT_DENOM),
+                })
+            );
+        }
+    }
+
+    // 测试查询合约配置
+    #[test]
+    fn config() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            commission_bps: 300,
+            lottery_caller: LotteryCaller::OwnerOnly,
+            denom: Some("uusd".to_string()),
+            max_bonus: Some(Uint128::new(1000)),
+            max_number: Some(50),
+            win_multiplier_bps: 15_000,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            ConfigResponse {
+                owner: Addr::unchecked("creator"),
+                single_round_only: false,
+                payout_delay_seconds: 0,
+                sweep_stray_into_bonus: false,
+                min_payout_amount: Uint128::zero(),
+                match_tolerance: Uint128::zero(),
+                bonus_growth_bps_per_round: 0,
+                lottery_caller: LotteryCaller::OwnerOnly,
+                max_metadata_len: DEFAULT_MAX_METADATA_LEN,
+                min_seconds_between_bonus: 0,
+                denom: "uusd".to_string(),
+                pause_on_insolvency: false,
+                commission_bps: 300,
+                daily_loss_limit: None,
+                beacon_root: None,
+                multi_player: false,
+                free_rounds: 0,
+                min_number: DEFAULT_MIN_NUMBER,
+                max_number: 50,
+                block_reset_until_claimed: false,
+                allow_guess_for: false,
+                guess_mode: GuessMode::Parity,
+                min_bonus: Uint128::zero(),
+                max_bonus: Some(Uint128::new(1000)),
+                rollover_min_edge_bps: None,
+                bet_duration_seconds: None,
+                min_distinct_winners: 0,
+                win_multiplier_bps: 15_000,
+                min_blocks: 0,
+                max_players: None,
+                reveal_timeout_seconds: DEFAULT_REVEAL_TIMEOUT_SECONDS,
+            },
+            value
+        );
+    }
+
+    // 测试查询数值限制
+    #[test]
+    fn limits() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            match_tolerance: Uint128::new(5),
+            min_payout_amount: Uint128::new(10),
+            daily_loss_limit: Some(Uint128::new(1000)),
+            commission_bps: 250,
+            min_seconds_between_bonus: 60,
+            max_number: Some(50),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetLimits {}).unwrap();
+        let value: LimitsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            LimitsResponse {
+                match_tolerance: Uint128::new(5),
+                min_payout_amount: Uint128::new(10),
+                daily_loss_limit: Some(Uint128::new(1000)),
+                commission_bps: 250,
+                max_metadata_len: 128,
+                min_seconds_between_bonus: 60,
+                min_number: 0,
+                max_number: 50,
+                min_bonus: Uint128::new(0),
+                max_bonus: None,
+            },
+            value
+        );
+    }
+
+    // 测试添加奖金返回累计的奖金
+    #[test]
+    fn add_bonus_data() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let mut totals = vec![];
+        for amount in [100, 50] {
+            let info = mock_info("creator", &coins(amount, DEFAULT_DENOM));
+            let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+            let value: BonusResponse = from_binary(&res.data.unwrap()).unwrap();
+            totals.push(value);
+        }
+        assert_eq!(
+            vec![
+                BonusResponse {
+                    total: Uint128::new(100),
+                    added: Uint128::new(100),
+                },
+                BonusResponse {
+                    total: Uint128::new(150),
+                    added: Uint128::new(50),
+                },
+            ],
+            totals
+        );
+    }
+
+    // 测试奖金的上下限
+    #[test]
+    fn bonus_bounds() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            min_bonus: Uint128::new(100),
+            max_bonus: Some(Uint128::new(50)),
+            ..InstantiateMsg::default()
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg);
+        match res {
+            Err(ContractError::InvalidConfig { .. }) => {}
+            _ => panic!("Must return invalid config error"),
+        }
+        let msg = InstantiateMsg {
+            min_bonus: Uint128::new(100),
+            max_bonus: Some(Uint128::new(300)),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        commit_number(&mut deps, 0);
+
+        // 奖金低于下限时不能开放
+        let info = mock_info("creator", &coins(60, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Start { metadata: None };
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+        match res {
+            Err(ContractError::BonusTooLow {}) => {}
+            _ => panic!("Must return bonus too low error"),
+        }
+
+        // 添加后超过上限被拒绝
+        let info = mock_info("creator", &coins(260, DEFAULT_DENOM));
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {});
+        match res {
+            Err(ContractError::BonusTooHigh { max }) => assert_eq!(300, max.u128()),
+            _ => panic!("Must return bonus too high error"),
+        }
+
+        let info = mock_info("creator", &coins(240, DEFAULT_DENOM));
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AddBonus {}).unwrap();
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    // 测试庄家获胜时按庄家优势决定奖金池是否留作下一轮
+    #[test]
+    fn rollover_by_edge() {
+        // 庄家优势不低于下限，奖金池留作下一轮的奖金
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            rollover_min_edge_bps: Some(0),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, true);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "player");
+        assert_eq!(0, res.messages.len());
+
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+        assert_eq!(0, res.messages.len());
         let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
         let value: StatusResponse = from_binary(&res).unwrap();
         assert_eq!(200, value.bonus.u128());
 
-        // 测试其他用户能否重置合约状态
-        let unauth_info = mock_info("anyone", &coins(2, DENOM));
-        let msg = ExecuteMsg::Reset { num: 5 };
-        let res = execute(deps.as_mut(), mock_env(), unauth_info, msg);
+        // 第一轮用户获胜，第二轮庄家获胜后庄家优势为0，低于下限时奖金池转给管理员
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            rollover_min_edge_bps: Some(1),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let mut last = None;
+        for is_odd in [false, true] {
+            let info = mock_info("creator", &[]);
+            execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+            open_round(&mut deps, 100);
+            place_guess(&mut deps, "player", 100, is_odd);
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+            reveal_number(&mut deps, 0);
+            last = Some(run_lottery(&mut deps, "player"));
+            deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+        }
+        assert_eq!(
+            last.unwrap().messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(200, DEFAULT_DENOM),
+            })
+        );
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetRealizedEdge {}).unwrap();
+        let value: RealizedEdgeResponse = from_binary(&res).unwrap();
+        assert_eq!("0", value.edge_bps);
+
+        // 多人模式下不能配置
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            multi_player: true,
+            rollover_min_edge_bps: Some(-100_000),
+            ..InstantiateMsg::default()
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg);
+        match res {
+            Err(ContractError::InvalidConfig { field }) => {
+                assert_eq!("rollover_min_edge_bps", field)
+            }
+            _ => panic!("Must return invalid config error"),
+        }
+    }
+
+    // 测试查询本轮玩家的下注
+    #[test]
+    fn current_bet() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetBet {}).unwrap();
+        let value: Option<BetResponse> = from_binary(&res).unwrap();
+        assert_eq!(None, value);
+
+        place_guess(&mut deps, "player", 100, true);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetBet {}).unwrap();
+        let value: Option<BetResponse> = from_binary(&res).unwrap();
+        assert_eq!(
+            Some(BetResponse {
+                user: Addr::unchecked("player"),
+                guess_is_odd: true,
+                paid: Uint128::new(100),
+            }),
+            value
+        );
+    }
+
+    // 测试查询执行操作的消息模板
+    #[test]
+    fn build_action() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 开放投注前不能下注
+        let msg = QueryMsg::BuildAction {
+            action: "guess".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: BuildActionResponse = from_binary(&res).unwrap();
+        assert!(!value.allowed);
+
+        open_round(&mut deps, 250);
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: BuildActionResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            BuildActionResponse {
+                action: Action::Guess,
+                allowed: true,
+                msg: ExecuteMsg::Guess { is_odd: false },
+                funds: coins(250, DEFAULT_DENOM),
+            },
+            value
+        );
+
+        // 玩家付了一部分后只需要补足剩下的金额
+        place_guess(&mut deps, "player", 100, true);
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: BuildActionResponse = from_binary(&res).unwrap();
+        assert_eq!(coins(150, DEFAULT_DENOM), value.funds);
+        place_guess(&mut deps, "player", 150, true);
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: BuildActionResponse = from_binary(&res).unwrap();
+        assert!(value.funds.is_empty());
+
+        // 猜单双玩法下不能猜数字
+        let msg = QueryMsg::BuildAction {
+            action: "guess_exact".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: BuildActionResponse = from_binary(&res).unwrap();
+        assert!(!value.allowed);
+
+        let msg = QueryMsg::BuildAction {
+            action: "unknown".to_string(),
+        };
+        assert!(query(deps.as_ref(), mock_env(), msg).is_err());
+    }
+
+    // 测试从 0.1 版本升级
+    #[test]
+    fn migrate_from_old_state() {
+        let mut deps = mock_dependencies(&[]);
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.1.0").unwrap();
+        let old = StateV0_1 {
+            guess_number: 3,
+            is_playing: true,
+            is_lottery: false,
+            guess_is_odd: true,
+            user: Some(Addr::unchecked("player")),
+            owner: Addr::unchecked("creator"),
+            bonus: Uint128::new(100),
+            user_payed: Uint128::new(100),
+        };
+        STATE_V0_1.save(&mut deps.storage, &old).unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(
+            vec![
+                attr("action", "migrate"),
+                attr("from_version", "0.1.0"),
+                attr("to_version", CONTRACT_VERSION),
+            ],
+            res.attributes
+        );
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(3, state.guess_number);
+        assert_eq!(Some(Addr::unchecked("player")), state.user);
+        assert_eq!(Addr::unchecked("creator"), state.owner);
+        assert_eq!(100, state.user_payed.u128());
+        assert_eq!(DEFAULT_DENOM, state.denom);
+        assert!(state.revealed);
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(CONTRACT_VERSION, version.version);
+
+        // 升级后旧的一轮可以正常开奖，数字为3，用户押单获胜
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        let res = run_lottery(&mut deps, "player");
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(200, DEFAULT_DENOM),
+            })
+        );
+
+        // 合约名称不一致时拒绝升级
+        set_contract_version(&mut deps.storage, "crates.io:other", "0.1.0").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {});
+        match res {
+            Err(ContractError::WrongContract { name }) => assert_eq!("crates.io:other", name),
+            _ => panic!("Must return wrong contract error"),
+        }
+
+        // 不能从更新的版本降级
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "9.0.0").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {});
+        match res {
+            Err(ContractError::CannotDowngrade { .. }) => {}
+            _ => panic!("Must return cannot downgrade error"),
+        }
+    }
+
+    // 测试截止下注的时间
+    #[test]
+    fn betting_deadline() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            bet_duration_seconds: Some(300),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatusCompact {}).unwrap();
+        let compact: Binary = from_binary(&res).unwrap();
+        let deadline = u64::from_be_bytes(compact[17..25].try_into().unwrap());
+        assert_eq!(mock_env().block.time.plus_seconds(300).seconds(), deadline);
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(300);
+        let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::Guess { is_odd: true });
+        match res {
+            Err(ContractError::BettingClosed {}) => {}
+            _ => panic!("Must return betting closed error"),
+        }
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(299);
+        let info = mock_info("player", &coins(100, DEFAULT_DENOM));
+        execute(deps.as_mut(), env, info, ExecuteMsg::Guess { is_odd: true }).unwrap();
+    }
+
+    // 测试玩家人数达到上限后新的玩家不能下注
+    #[test]
+    fn max_players() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            multi_player: true,
+            max_players: Some(2),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "alice", 100, false);
+        place_guess(&mut deps, "bob", 100, true);
+
+        let info = mock_info("carol", &coins(100, DEFAULT_DENOM));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::Guess { is_odd: true },
+        );
+        match res {
+            Err(ContractError::RoundFull {}) => {}
+            _ => panic!("Must return round full error"),
+        }
+
+        // 已下注的玩家仍可以修改单双
+        place_guess(&mut deps, "alice", 0, true);
+        let bet = BETS.load(&deps.storage, &Addr::unchecked("alice")).unwrap();
+        assert!(bet.is_odd);
+        assert_eq!(100, bet.stake.u128());
+        assert_eq!(2, STATE.load(&deps.storage).unwrap().player_count);
+
+        // 开奖后人数清零，下一轮新的玩家可以下注
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(300, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        run_lottery(&mut deps, "anyone");
+        assert_eq!(0, STATE.load(&deps.storage).unwrap().player_count);
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "carol", 100, true);
+        assert_eq!(1, STATE.load(&deps.storage).unwrap().player_count);
+    }
+
+    // 测试猜对的玩家人数不足时本轮作废
+    #[test]
+    fn min_distinct_winners() {
+        let msg = InstantiateMsg {
+            multi_player: true,
+            min_distinct_winners: 2,
+            ..InstantiateMsg::default()
+        };
+
+        // 数字为0，只有 alice 押双，本轮作废
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "alice", 100, false);
+        place_guess(&mut deps, "bob", 100, true);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(300, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "anyone");
+        assert_eq!(attr("voided", "true"), res.attributes[2]);
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(100, DEFAULT_DENOM),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(100, DEFAULT_DENOM),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "creator".to_string(),
+                    amount: coins(100, DEFAULT_DENOM),
+                }),
+            ],
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>()
+        );
+
+        // 数字为0，alice 和 bob 都押单，没有人猜对也作废
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "alice", 100, true);
+        place_guess(&mut deps, "bob", 100, true);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(300, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "anyone");
+        assert_eq!(attr("winners", "0"), res.attributes[1]);
+        assert_eq!(attr("voided", "true"), res.attributes[2]);
+        assert_eq!(
+            vec![
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(100, DEFAULT_DENOM),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(100, DEFAULT_DENOM),
+                }),
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "creator".to_string(),
+                    amount: coins(100, DEFAULT_DENOM),
+                }),
+            ],
+            res.messages
+                .into_iter()
+                .map(|msg| msg.msg)
+                .collect::<Vec<_>>()
+        );
+
+        // alice 和 bob 都押双，正常瓜分奖金池
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "alice", 100, false);
+        place_guess(&mut deps, "bob", 100, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(300, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+        let res = run_lottery(&mut deps, "anyone");
+        assert_eq!(attr("winners", "2"), res.attributes[1]);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(150, DEFAULT_DENOM),
+            })
+        );
+    }
+
+    // 测试阶段转换图随配置变化
+    #[test]
+    fn transition_graph() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 提交和揭示承诺不改变阶段，开放投注和开奖需要满足相应的条件
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetTransitionGraph {}).unwrap();
+        let value: TransitionGraphResponse = from_binary(&res).unwrap();
+        let commit = Transition {
+            from: Phase::Setup,
+            action: Action::Commit,
+            to: Phase::Setup,
+            guards: vec![],
+        };
+        let reveal = Transition {
+            from: Phase::Betting,
+            action: Action::Reveal,
+            to: Phase::Betting,
+            guards: vec![Guard::Unrevealed, Guard::BetComplete],
+        };
+        let start = Transition {
+            from: Phase::Setup,
+            action: Action::Start,
+            to: Phase::Betting,
+            guards: vec![Guard::Committed],
+        };
+        let lottery = Transition {
+            from: Phase::Betting,
+            action: Action::Lottery,
+            to: Phase::Resolved,
+            guards: vec![Guard::BetComplete, Guard::Revealed],
+        };
+        assert!(value.transitions.contains(&commit));
+        assert!(value.transitions.contains(&reveal));
+        assert!(value.transitions.contains(&start));
+        assert!(value.transitions.contains(&lottery));
+        assert!(!value
+            .transitions
+            .iter()
+            .any(|transition| transition.action == Action::GuessExact));
+
+        // 使用信标的随机数时不需要提交和揭示承诺，单轮模式下开奖后不能重置
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            beacon_root: Some(Binary::from(vec![0u8; 32])),
+            bet_duration_seconds: Some(300),
+            single_round_only: true,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetTransitionGraph {}).unwrap();
+        let value: TransitionGraphResponse = from_binary(&res).unwrap();
+        assert!(!value.transitions.contains(&commit));
+        assert!(!value.transitions.contains(&reveal));
+        assert!(value.transitions.contains(&Transition {
+            guards: vec![],
+            ..start
+        }));
+        assert!(value.transitions.contains(&Transition {
+            guards: vec![Guard::BettingClosed, Guard::BetComplete],
+            ..lottery
+        }));
+        assert!(!value.transitions.contains(&Transition {
+            from: Phase::Resolved,
+            action: Action::Reset,
+            to: Phase::Setup,
+            guards: vec![],
+        }));
+    }
+
+    // 测试开放投注后至少经过指定的区块数才能开奖
+    #[test]
+    fn min_blocks_before_lottery() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            min_blocks: 3,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 200);
+        place_guess(&mut deps, "player", 200, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(400, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+
+        let msg = ExecuteMsg::Lottery {
+            randomness: None,
+            proof: vec![],
+        };
+        let mut env = mock_env();
+        env.block.height += 2;
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg.clone());
+        match res {
+            Err(ContractError::TooSoon {}) => {}
+            _ => panic!("Must return too soon error"),
+        }
+
+        env.block.height += 1;
+        let info = mock_info("player", &[]);
+        execute(deps.as_mut(), env, info, msg).unwrap();
+    }
+
+    // 测试截止下注后任何人都可以开奖
+    #[test]
+    fn lottery_after_deadline() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            lottery_caller: LotteryCaller::OwnerOnly,
+            bet_duration_seconds: Some(300),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        reveal_number(&mut deps, 0);
+
+        let msg = ExecuteMsg::Lottery {
+            randomness: None,
+            proof: vec![],
+        };
+        let info = mock_info("stranger", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
         match res {
             Err(ContractError::Unauthorized {}) => {}
             _ => panic!("Must return unauthorized error"),
         }
 
-        // 管理员重置合约
-        let auth_info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::Reset { num: 5 };
-        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        // 权限查询与执行结果一致
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(300);
+        let check = QueryMsg::CheckAuthorization {
+            address: "stranger".to_string(),
+            action: "lottery".to_string(),
+        };
+        let res = query(deps.as_ref(), mock_env(), check.clone()).unwrap();
+        let value: AuthorizationResponse = from_binary(&res).unwrap();
+        assert!(!value.authorized);
+        let res = query(deps.as_ref(), env.clone(), check).unwrap();
+        let value: AuthorizationResponse = from_binary(&res).unwrap();
+        assert!(value.authorized);
 
-        // 重置后奖金应为0
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetStatus {}).unwrap();
-        let value: StatusResponse = from_binary(&res).unwrap();
-        assert_eq!(0, value.bonus.u128());
+        let info = mock_info("stranger", &[]);
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(200, DEFAULT_DENOM),
+            })
+        );
+    }
+
+    // 测试管理员超时不揭示数字时按玩家猜对结算
+    #[test]
+    fn reveal_timeout() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            bet_duration_seconds: Some(300),
+            reveal_timeout_seconds: Some(600),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 数字为1，用户押双会输，管理员不揭示
+        commit_number(&mut deps, 1);
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+
+        let msg = ExecuteMsg::Lottery {
+            randomness: None,
+            proof: vec![],
+        };
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(899);
+        let info = mock_info("stranger", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg.clone());
+        match res {
+            Err(ContractError::NotRevealed {}) => {}
+            _ => panic!("Must return not revealed error"),
+        }
+
+        // 超时后任何人都可以开奖，奖金归玩家
+        env.block.time = env.block.time.plus_seconds(1);
+        let info = mock_info("stranger", &[]);
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        assert_eq!(attr("winner", "player"), res.attributes[1]);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "player".to_string(),
+                amount: coins(200, DEFAULT_DENOM),
+            })
+        );
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetResult {}).unwrap();
+        let value: Option<ResultResponse> = from_binary(&res).unwrap();
+        assert!(value.unwrap().user_won);
+    }
+
+    // 测试佣金跨轮累计后由管理员提取
+    #[test]
+    fn withdraw_fees() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            commission_bps: 500,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::WithdrawFees {});
+        match res {
+            Err(ContractError::NoFees {}) => {}
+            _ => panic!("Must return no fees error"),
+        }
+
+        // 两轮用户都获胜，佣金分别为 50 和 100，重置时不转给管理员
+        for bonus in [1000u128, 2000] {
+            let info = mock_info("creator", &[]);
+            let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+            assert_eq!(0, res.messages.len());
+            let fees = STATE.load(&deps.storage).unwrap().accrued_fees.u128();
+            open_round(&mut deps, bonus);
+            place_guess(&mut deps, "player", bonus, false);
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(fees + bonus * 2, DEFAULT_DENOM));
+            reveal_number(&mut deps, 0);
+            let res = run_lottery(&mut deps, "player");
+            assert_eq!(
+                res.messages[0].msg,
+                CosmosMsg::Bank(BankMsg::Send {
+                    to_address: "player".to_string(),
+                    amount: coins(bonus * 2 - bonus / 20, DEFAULT_DENOM),
+                })
+            );
+            deps.querier
+                .update_balance(MOCK_CONTRACT_ADDR, coins(fees + bonus / 20, DEFAULT_DENOM));
+        }
+
+        // 进行中的一轮不受影响
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reset {}).unwrap();
+        open_round(&mut deps, 100);
+        let info = mock_info("player", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::WithdrawFees {});
+        match res {
+            Err(ContractError::Unauthorized {}) => {}
+            _ => panic!("Must return unauthorized error"),
+        }
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::WithdrawFees {}).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(150, DEFAULT_DENOM),
+            })
+        );
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(0, state.accrued_fees.u128());
+        assert_eq!(100, state.bonus.u128());
+        assert!(state.is_playing);
+    }
+
+    // 测试开奖结果通过 data 返回
+    #[test]
+    fn lottery_result_data() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        commit_number(&mut deps, 5);
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        reveal_number(&mut deps, 5);
+        let res = run_lottery(&mut deps, "player");
+
+        let result: LotteryResult = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(
+            LotteryResult {
+                guess_number: 5,
+                user_won: false,
+                winner: Addr::unchecked("creator"),
+                payout: Uint128::new(200),
+                winner_amount: Uint128::new(200),
+            },
+            result
+        );
+
+        // 按倍数获奖并抽取佣金时，赢家获得的金额小于奖金池
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            commission_bps: 500,
+            win_multiplier_bps: 15_000,
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        commit_number(&mut deps, 4);
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, false);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        reveal_number(&mut deps, 4);
+        let res = run_lottery(&mut deps, "player");
+
+        let result: LotteryResult = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(
+            LotteryResult {
+                guess_number: 4,
+                user_won: true,
+                winner: Addr::unchecked("player"),
+                payout: Uint128::new(200),
+                winner_amount: Uint128::new(145),
+            },
+            result
+        );
+    }
+
+    // 测试查询本轮的开奖结果
+    #[test]
+    fn round_result() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        commit_number(&mut deps, 5);
+        open_round(&mut deps, 100);
+        place_guess(&mut deps, "player", 100, true);
+        deps.querier
+            .update_balance(MOCK_CONTRACT_ADDR, coins(200, DEFAULT_DENOM));
+        reveal_number(&mut deps, 5);
+
+        // 开奖前没有结果
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetResult {}).unwrap();
+        let value: Option<ResultResponse> = from_binary(&res).unwrap();
+        assert_eq!(None, value);
+
+        run_lottery(&mut deps, "player");
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetResult {}).unwrap();
+        let value: Option<ResultResponse> = from_binary(&res).unwrap();
+        assert_eq!(
+            Some(ResultResponse {
+                user_won: true,
+                winner: Addr::unchecked("player"),
+                guess_number: 5,
+            }),
+            value
+        );
     }
 }
@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -20,6 +20,111 @@ pub enum ContractError {
 
     #[error("Pay error")]
     Pay {},
+
+    #[error("Bet not fully paid")]
+    BetIncomplete {},
+
+    #[error("Contract retired")]
+    Retired {},
+
+    #[error("Invalid config: {field}")]
+    InvalidConfig { field: String },
+
+    #[error("Metadata longer than {max} bytes")]
+    MetadataTooLong { max: u32 },
+
+    #[error("Too fast")]
+    TooFast {},
+
+    #[error("Too soon")]
+    TooSoon {},
+
+    #[error("No bonus")]
+    NoBonus {},
+
+    #[error("Bonus too low")]
+    BonusTooLow {},
+
+    #[error("Bonus exceeds {max}")]
+    BonusTooHigh { max: Uint128 },
+
+    #[error("Nothing to claim")]
+    NothingToClaim {},
+
+    #[error("Payout is still cooling down")]
+    CoolingDown {},
+
+    #[error("Betting is paused")]
+    Paused {},
+
+    #[error("Contract is insolvent")]
+    Insolvent {},
+
+    #[error("Daily loss limit reached")]
+    LossLimitReached {},
+
+    #[error("No commitment")]
+    NoCommitment {},
+
+    #[error("Reveal does not match the commitment")]
+    HashMismatch {},
+
+    #[error("Secret is not a number")]
+    InvalidSecret {},
+
+    #[error("Number {num} out of range")]
+    InvalidNumber { num: i8 },
+
+    #[error("Winnings not claimed yet")]
+    UnclaimedWinnings {},
+
+    #[error("Funds not accepted")]
+    UnexpectedFunds {},
+
+    #[error("Betting on behalf of others is disabled")]
+    GuessForDisabled {},
+
+    #[error("No fees to withdraw")]
+    NoFees {},
+
+    #[error("No reserve to withdraw")]
+    NoReserve {},
+
+    #[error("Betting closed")]
+    BettingClosed {},
+
+    #[error("Betting still open")]
+    BettingOpen {},
+
+    #[error("Not available in this guess mode")]
+    WrongGuessMode {},
+
+    #[error("Cannot migrate from contract {name}")]
+    WrongContract { name: String },
+
+    #[error("Invalid contract version {version}")]
+    InvalidVersion { version: String },
+
+    #[error("Cannot migrate from newer version {version}")]
+    CannotDowngrade { version: String },
+
+    #[error("Round already settled")]
+    RoundSettled {},
+
+    #[error("Round full")]
+    RoundFull {},
+
+    #[error("Number not revealed")]
+    NotRevealed {},
+
+    #[error("Number already revealed")]
+    AlreadyRevealed {},
+
+    #[error("Invalid randomness proof")]
+    BadProof {},
+
+    #[error("Number is drawn from the beacon")]
+    BeaconConfigured {},
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
@@ -1,3 +1,5 @@
+pub mod action;
+pub mod beacon;
 pub mod coin_helper;
 pub mod contract;
 mod error;
@@ -1,18 +1,135 @@
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Addr, Binary, Coin, Decimal, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct InstantiateMsg {}
+use crate::action::{Action, Caller, Guard};
+use crate::state::{GuessMode, LotteryCaller, Phase, RoundRecord};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    // 只进行一轮游戏，开奖后不能再重置
+    #[serde(default)]
+    pub single_round_only: bool,
+    // 用户赢得的奖金托管多少秒后才能领取，0 表示开奖时直接发放
+    #[serde(default)]
+    pub payout_delay_seconds: u64,
+    // 开放投注时把合约中未记账的余额并入奖金
+    #[serde(default)]
+    pub sweep_stray_into_bonus: bool,
+    // 低于该金额的奖金不直接转账，记入赢家的待领取余额
+    #[serde(default)]
+    pub min_payout_amount: Uint128,
+    // 下注金额和奖金的差额在该范围内视为足额，默认必须完全一致
+    #[serde(default)]
+    pub match_tolerance: Uint128,
+    // 每轮建议奖金的增长比例，单位为万分之一，最大为 10000
+    #[serde(default)]
+    pub bonus_growth_bps_per_round: u16,
+    // 谁可以开奖，默认任何人都可以
+    #[serde(default)]
+    pub lottery_caller: LotteryCaller,
+    // 每轮备注信息的最大长度，默认为 128
+    pub max_metadata_len: Option<u32>,
+    // 两次添加奖金之间至少间隔的秒数
+    #[serde(default)]
+    pub min_seconds_between_bonus: u64,
+    // 下注和奖金使用的币种，默认为 uluna
+    pub denom: Option<String>,
+    // 发现余额不足以覆盖已记账的资金时自动暂停押注
+    #[serde(default)]
+    pub pause_on_insolvency: bool,
+    // 用户获胜时庄家从奖金中抽取的佣金比例，单位为万分之一，最大为 10000
+    #[serde(default)]
+    pub commission_bps: u16,
+    // 每个地址每天最多可以输掉的金额，默认不限制
+    pub daily_loss_limit: Option<Uint128>,
+    // 链下随机数信标的默克尔根，设置后用信标的随机数开奖，不再需要提交承诺，需要同时配置 bet_duration_seconds
+    pub beacon_root: Option<Binary>,
+    // 多人模式，每轮可以有多个玩家各自下注，猜对的玩家按下注金额瓜分奖金池
+    #[serde(default)]
+    pub multi_player: bool,
+    // 玩家的前几轮免收佣金
+    #[serde(default)]
+    pub free_rounds: u32,
+    // 本轮数字的最小值，默认为 0
+    pub min_number: Option<i8>,
+    // 本轮数字的最大值，默认为 100
+    pub max_number: Option<i8>,
+    // 还有待领取的奖金时不能重置
+    #[serde(default)]
+    pub block_reset_until_claimed: bool,
+    // 允许代他人下注
+    #[serde(default)]
+    pub allow_guess_for: bool,
+    // 玩法，默认猜单双，猜数字玩法不支持多人模式
+    #[serde(default)]
+    pub guess_mode: GuessMode,
+    // 开放投注时奖金至少为该金额
+    #[serde(default)]
+    pub min_bonus: Uint128,
+    // 奖金的上限，默认不限制
+    pub max_bonus: Option<Uint128>,
+    // 庄家获胜且庄家优势不低于该值时奖金池留作下一轮的奖金，单位为万分之一，默认不留，多人模式下不能配置
+    pub rollover_min_edge_bps: Option<i64>,
+    // 开放投注后多少秒截止下注，默认不截止
+    pub bet_duration_seconds: Option<u64>,
+    // 多人模式下猜对的玩家少于该人数时本轮作废，退还所有下注
+    #[serde(default)]
+    pub min_distinct_winners: u32,
+    // 单人模式下赢家获得本金的倍数，以万分之一为单位，0 表示赢家获得全部奖金池
+    #[serde(default)]
+    pub win_multiplier_bps: u16,
+    // 开放投注后至少经过多少个区块才能开奖
+    #[serde(default)]
+    pub min_blocks: u64,
+    // 多人模式下每轮最多的玩家人数，默认不限制
+    pub max_players: Option<u32>,
+    // 截止下注后等待管理员揭示数字的秒数，没有截止时间时从开放投注时开始计算，超时后所有玩家按猜对结算，默认为一天
+    pub reveal_timeout_seconds: Option<u64>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    Guess { is_odd: bool },
+    Guess {
+        is_odd: bool,
+    },
+    GuessFor {
+        beneficiary: String,
+        is_odd: bool,
+    },
+    GuessExact {
+        number: i8,
+    },
     AddBonus {},
-    Reset { num: i8 },
-    Start {},
-    Lottery {},
+    Reset {},
+    Commit {
+        hash: String,
+    },
+    Reveal {
+        secret: String,
+        nonce: String,
+    },
+    TransferOwnership {
+        new_owner: String,
+    },
+    AcceptOwnership {},
+    Start {
+        metadata: Option<String>,
+    },
+    Lottery {
+        // 配置了 beacon_root 时必须提供本轮的随机数和默克尔证明
+        randomness: Option<Binary>,
+        #[serde(default)]
+        proof: Vec<Binary>,
+    },
+    Claim {},
+    Resume {},
+    TopUp {},
+    WithdrawFees {},
+    CollectDust {},
+    CancelRound {},
+    WithdrawReserve {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -20,6 +137,70 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
     // 查询合约状态
     GetStatus {},
+    // 查询紧凑编码的合约状态，布局见 `query_status_compact`
+    GetStatusCompact {},
+    // 查询每个执行操作可执行的阶段和调用者
+    GetActionSpec {},
+    // 查询执行某个操作需要发送的消息模板和附带的资金
+    BuildAction {
+        action: String,
+    },
+    // 查询当前配置下所有合法的阶段转换
+    GetTransitionGraph {},
+    // 查询资金流水统计
+    GetFlowStats {},
+    // 查询地址在当前阶段能否执行某个操作
+    CheckAuthorization {
+        address: String,
+        action: String,
+    },
+    // 对比已记账金额和合约实际余额
+    Reconcile {},
+    // 查询每个地址可能需要支付的金额
+    GetLiabilities {},
+    // 查询下一轮的建议奖金
+    GetSuggestedBonus {},
+    // 查询合约状态的摘要
+    GetStateDigest {},
+    // 查询合约持有的所有币种余额
+    GetAllBalances {},
+    // 查询合约持有的投注币种余额
+    GetBalance {},
+    // 查询所有负债同时兑付时合约能否偿付
+    IsWorstCaseSolvent {},
+    // 查询实际的庄家优势
+    GetRealizedEdge {},
+    // 查询完整的游戏状态
+    GetState {},
+    // 查询暂停押注的原因
+    GetPauseReason {},
+    // 查询本轮已进行的时间和下注速度
+    GetRoundMetrics {},
+    // 查询承诺的计算方式
+    GetCommitmentPreimageFormat {},
+    // 查询历史开奖数字的单双分布
+    GetDrawHistogram {},
+    // 一次查询合约配置的所有数值限制
+    GetLimits {},
+    // 查询合约初始化时的所有配置
+    GetConfig {},
+    // 查询本轮玩家的下注，还没有玩家下注时为空
+    GetBet {},
+    // 查询本轮的开奖结果，还没有开奖时为空
+    GetResult {},
+    // 查询一轮的开奖记录
+    GetRound {
+        id: u64,
+    },
+    // 分页查询历史开奖记录
+    ListRounds {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    // 查询地址的累计统计，没有参与过开奖时为默认值
+    GetUserStats {
+        address: String,
+    },
 }
 
 // 响应查询结果的结构体
@@ -27,6 +208,241 @@ pub enum QueryMsg {
 pub struct StatusResponse {
     pub playing: bool,
     pub bonus: Uint128,
+    pub metadata: Option<String>,
+}
+
+// 单个执行操作的规则
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ActionSpec {
+    pub action: Action,
+    pub phases: Vec<Phase>,
+    pub caller: Caller,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ActionSpecResponse {
+    pub actions: Vec<ActionSpec>,
+}
+
+// 在 from 阶段满足 guards 的条件时执行 action 后进入 to 阶段
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Transition {
+    pub from: Phase,
+    pub action: Action,
+    pub to: Phase,
+    pub guards: Vec<Guard>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransitionGraphResponse {
+    pub transitions: Vec<Transition>,
+}
+
+// 执行操作的消息模板，参数需要由客户端填写，allowed 表示当前阶段能否执行
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BuildActionResponse {
+    pub action: Action,
+    pub allowed: bool,
+    pub msg: ExecuteMsg,
+    pub funds: Vec<Coin>,
+}
+
+// 资金流水统计
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FlowStatsResponse {
+    pub total_staked_ever: Uint128,
+    pub total_bonus_ever: Uint128,
+    pub total_paid_ever: Uint128,
+    pub currently_locked: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuthorizationResponse {
+    pub authorized: bool,
+}
+
+// 对账结果，drift 为实际余额减去已记账金额
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReconcileResponse {
+    pub tracked: Uint128,
+    pub actual: Uint128,
+    pub drift: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LiabilitiesResponse {
+    pub liabilities: Vec<(Addr, Uint128)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SuggestedBonusResponse {
+    pub base_bonus: Uint128,
+    pub rounds_started: u64,
+    pub suggested_bonus: Uint128,
+}
+
+// 合约状态的 SHA-256，十六进制编码
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StateDigestResponse {
+    pub digest: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllBalancesResponse {
+    pub balances: Vec<Coin>,
+}
+
+// 投注币种的实际余额，可能和记账的奖金不一致
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BalanceResponse {
+    pub denom: String,
+    pub amount: Uint128,
+}
+
+// 偿付能力，shortfall 为余额不足的部分
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SolvencyResponse {
+    pub solvent: bool,
+    pub shortfall: Uint128,
+}
+
+// 实际的庄家优势，edge_bps 为带符号的万分比
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RealizedEdgeResponse {
+    pub total_house_income: Uint128,
+    pub total_house_payouts: Uint128,
+    pub total_staked: Uint128,
+    pub edge_bps: String,
+}
+
+// 完整的游戏状态，guess_number 只在开奖后返回
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FullStateResponse {
+    pub guess_number: Option<i8>,
+    pub guess_is_odd: bool,
+    pub user: Option<Addr>,
+    pub user_payed: Uint128,
+    pub is_lottery: bool,
+    pub owner: Addr,
+}
+
+// 是否暂停押注以及暂停的原因
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PauseReasonResponse {
+    pub paused: bool,
+    pub reason: Option<String>,
+}
+
+// 本轮的实时统计，bets_per_minute 为每分钟的下注次数
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoundMetricsResponse {
+    pub elapsed_seconds: u64,
+    pub bets_placed: u64,
+    pub total_staked: Uint128,
+    pub bets_per_minute: Decimal,
+}
+
+// 开奖结果，作为 Lottery 的 Response.data 返回，payout 为本轮的奖金池
+// winner_amount 为扣除佣金和庄家份额后记给赢家的金额，奖金池留作下一轮时为 0
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LotteryResult {
+    pub guess_number: i8,
+    pub user_won: bool,
+    pub winner: Addr,
+    pub payout: Uint128,
+    pub winner_amount: Uint128,
+}
+
+// 添加奖金后的奖金，作为 AddBonus 的 Response.data 返回
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BonusResponse {
+    pub total: Uint128,
+    pub added: Uint128,
+}
+
+// 本轮的开奖结果
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ResultResponse {
+    pub user_won: bool,
+    pub winner: Addr,
+    pub guess_number: i8,
+}
+
+// 本轮玩家的下注
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BetResponse {
+    pub user: Addr,
+    pub guess_is_odd: bool,
+    pub paid: Uint128,
+}
+
+// 合约的配置，初始化时设置的参数，没有设置的参数为默认值
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigResponse {
+    pub owner: Addr,
+    pub single_round_only: bool,
+    pub payout_delay_seconds: u64,
+    pub sweep_stray_into_bonus: bool,
+    pub min_payout_amount: Uint128,
+    pub match_tolerance: Uint128,
+    pub bonus_growth_bps_per_round: u16,
+    pub lottery_caller: LotteryCaller,
+    pub max_metadata_len: u32,
+    pub min_seconds_between_bonus: u64,
+    pub denom: String,
+    pub pause_on_insolvency: bool,
+    pub commission_bps: u16,
+    pub daily_loss_limit: Option<Uint128>,
+    pub beacon_root: Option<Binary>,
+    pub multi_player: bool,
+    pub free_rounds: u32,
+    pub min_number: i8,
+    pub max_number: i8,
+    pub block_reset_until_claimed: bool,
+    pub allow_guess_for: bool,
+    pub guess_mode: GuessMode,
+    pub min_bonus: Uint128,
+    pub max_bonus: Option<Uint128>,
+    pub rollover_min_edge_bps: Option<i64>,
+    pub bet_duration_seconds: Option<u64>,
+    pub min_distinct_winners: u32,
+    pub win_multiplier_bps: u16,
+    pub min_blocks: u64,
+    pub max_players: Option<u32>,
+    pub reveal_timeout_seconds: u64,
+}
+
+// 合约配置的数值限制，没有配置的限制为空
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LimitsResponse {
+    pub match_tolerance: Uint128,
+    pub min_payout_amount: Uint128,
+    pub daily_loss_limit: Option<Uint128>,
+    pub commission_bps: u16,
+    pub max_metadata_len: u32,
+    pub min_seconds_between_bonus: u64,
+    pub min_number: i8,
+    pub max_number: i8,
+    pub min_bonus: Uint128,
+    pub max_bonus: Option<Uint128>,
+}
+
+// 承诺的计算方式：fields 按顺序以 encoding 编码后用 separator 拼接，
+// 求 hash 后按 output 编码
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CommitmentFormatResponse {
+    pub fields: Vec<String>,
+    pub separator: String,
+    pub secret_format: String,
+    pub encoding: String,
+    pub hash: String,
+    pub output: String,
+}
+
+// 历史开奖记录和对应的编号
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoundsResponse {
+    pub rounds: Vec<(u64, RoundRecord)>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -1,13 +1,13 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Uint128};
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Binary, Timestamp, Uint128};
+use cw_storage_plus::{Item, Map, U64Key};
 
 /// 合约状态信息
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
-    /// 管理员设置的数字
+    /// 本轮的数字，管理员揭示后才有意义
     pub guess_number: i8,
 
     /// 游戏是否在进行
@@ -30,6 +30,393 @@ pub struct State {
 
     /// 用户已付金额
     pub user_payed: Uint128,
+
+    /// 是否只进行一轮游戏
+    pub single_round_only: bool,
+
+    /// 奖金托管的秒数，0 表示开奖时直接发放
+    pub payout_delay_seconds: u64,
+
+    /// 托管中等待领取的奖金总额
+    pub escrowed: Uint128,
+
+    /// 开放投注时是否把未记账的余额并入奖金
+    pub sweep_stray_into_bonus: bool,
+
+    /// 最小转账金额，低于该金额的奖金记入待领取余额
+    pub min_payout_amount: Uint128,
+
+    /// 下注金额和奖金允许的差额
+    pub match_tolerance: Uint128,
+
+    /// 每轮建议奖金的增长比例，单位为万分之一
+    pub bonus_growth_bps_per_round: u16,
+
+    /// 建议奖金的基数，即第一轮的奖金
+    pub base_bonus: Uint128,
+
+    /// 已开放的轮数
+    pub rounds_started: u64,
+
+    /// 下一轮的建议奖金
+    pub suggested_bonus: Uint128,
+
+    /// 谁可以开奖
+    pub lottery_caller: LotteryCaller,
+
+    /// 每轮备注信息的最大长度
+    pub max_metadata_len: u32,
+
+    /// 本轮的备注信息
+    pub metadata: Option<String>,
+
+    /// 两次添加奖金之间至少间隔的秒数
+    pub min_seconds_between_bonus: u64,
+
+    /// 上次添加奖金的时间
+    pub last_bonus_time: Option<Timestamp>,
+
+    /// 下注和奖金使用的币种
+    pub denom: String,
+
+    /// 发现余额不足时是否自动暂停押注
+    pub pause_on_insolvency: bool,
+
+    /// 押注是否已暂停
+    pub paused: bool,
+
+    /// 暂停押注的原因
+    pub pause_reason: Option<String>,
+
+    /// 用户获胜时庄家抽取的佣金比例，单位为万分之一
+    pub commission_bps: u16,
+
+    /// 每个地址每天最多可以输掉的金额
+    pub daily_loss_limit: Option<Uint128>,
+
+    /// 管理员提交的 `sha256(secret || nonce)`，十六进制编码
+    pub commit_hash: Option<String>,
+
+    /// 本轮的数字是否已揭示
+    pub revealed: bool,
+
+    /// 链下随机数信标的默克尔根
+    pub beacon_root: Option<Binary>,
+
+    /// 等待接受的新管理员
+    pub proposed_owner: Option<Addr>,
+
+    /// 本轮开放投注的时间
+    pub round_started_at: Option<Timestamp>,
+
+    /// 本轮接受的下注次数
+    pub bets_placed: u64,
+
+    /// 是否为多人模式，多人模式下 `user` 为空，`user_payed` 为所有玩家下注的总额
+    pub multi_player: bool,
+
+    /// 玩家的前几轮免收佣金
+    pub free_rounds: u32,
+
+    /// 已开奖的轮数，也是最近一轮开奖记录的编号
+    pub round_count: u64,
+
+    /// 本轮数字允许的最小值
+    pub min_number: i8,
+
+    /// 本轮数字允许的最大值
+    pub max_number: i8,
+
+    /// 还有待领取的奖金时不能重置
+    pub block_reset_until_claimed: bool,
+
+    /// 允许代他人下注
+    pub allow_guess_for: bool,
+
+    /// 玩法
+    pub guess_mode: GuessMode,
+
+    /// 猜数字玩法下玩家猜的数字
+    pub guessed_number: i8,
+
+    /// 开放投注时奖金至少为该金额
+    pub min_bonus: Uint128,
+
+    /// 奖金的上限
+    pub max_bonus: Option<Uint128>,
+
+    /// 留作下一轮奖金的庄家优势下限，单位为万分之一
+    pub rollover_min_edge_bps: Option<i64>,
+
+    /// 上一轮留作下一轮奖金的奖金池，重置时转为奖金
+    pub rollover_bonus: Uint128,
+
+    /// 开放投注后多少秒截止下注
+    pub bet_duration_seconds: Option<u64>,
+
+    /// 本轮截止下注的时间
+    pub bet_deadline: Option<Timestamp>,
+
+    /// 多人模式下猜对的玩家少于该人数时本轮作废
+    pub min_distinct_winners: u32,
+
+    /// 累计还没有提取的佣金
+    pub accrued_fees: Uint128,
+
+    /// 赢家获得本金的倍数，以万分之一为单位，0 表示赢家获得全部奖金池
+    pub win_multiplier_bps: u16,
+
+    /// 开放投注后至少经过多少个区块才能开奖
+    pub min_blocks: u64,
+
+    /// 本轮开放投注时的区块高度
+    pub start_height: Option<u64>,
+
+    /// 单人模式下玩家是否已付清下注
+    pub bet_complete: bool,
+
+    /// 多人模式下每轮最多的玩家人数
+    pub max_players: Option<u32>,
+
+    /// 截止下注后等待管理员揭示数字的秒数，超时后按玩家猜对结算
+    pub reveal_timeout_seconds: u64,
+
+    /// 本轮是否因管理员超时没有揭示而按玩家猜对结算
+    pub forfeited: bool,
+
+    /// 管理员通过 `TopUp` 补充的备用金，不参与开奖分配
+    pub reserve: Uint128,
+
+    /// 多人模式下本轮已下注的玩家人数
+    pub player_count: u32,
+}
+
+impl State {
+    /// 合约中已记账的资金
+    ///
+    /// 应付的资金加上管理员补充的备用金
+    pub fn locked_funds(&self) -> Uint128 {
+        self.owed_funds() + self.reserve
+    }
+
+    /// 合约应付的资金
+    ///
+    /// 开奖前包括奖金和用户已付金额，另加托管中等待领取的奖金、留作下一轮的奖金池和累计的佣金
+    pub fn owed_funds(&self) -> Uint128 {
+        let round = if self.is_lottery {
+            Uint128::new(0)
+        } else {
+            self.bonus + self.user_payed
+        };
+        round + self.escrowed + self.rollover_bonus + self.accrued_fees
+    }
+
+    /// 本轮的玩家是否猜对
+    ///
+    /// 猜单双玩法下单双一致，猜数字玩法下数字一致，管理员超时没有揭示时总是猜对
+    pub fn user_won(&self) -> bool {
+        if self.forfeited {
+            return true;
+        }
+        match self.guess_mode {
+            GuessMode::Parity => self.guess_is_odd == (self.guess_number % 2 != 0),
+            GuessMode::Exact => self.guessed_number == self.guess_number,
+        }
+    }
+
+    /// 本轮是否已有玩家下注
+    pub fn has_player(&self) -> bool {
+        self.user.is_some() || self.player_count > 0
+    }
+
+    /// 本轮是否已经截止下注
+    ///
+    /// 没有截止时间时，揭示超时后也不能再下注，避免玩家在超时后下注直接按猜对结算
+    pub fn betting_closed(&self, now: Timestamp) -> bool {
+        match self.bet_deadline {
+            Some(deadline) => now >= deadline,
+            None => self.reveal_expired(now),
+        }
+    }
+
+    /// 截止下注后超过 `reveal_timeout_seconds` 管理员仍没有揭示数字
+    ///
+    /// 没有截止时间时从开放投注时开始计算；使用信标的随机数时任何人都可以开奖，不需要管理员揭示
+    pub fn reveal_expired(&self, now: Timestamp) -> bool {
+        self.beacon_root.is_none()
+            && !self.revealed
+            && matches!(self.bet_deadline.or(self.round_started_at), Some(since)
+                if now >= since.plus_seconds(self.reveal_timeout_seconds))
+    }
+
+    /// 根据状态标志得出当前的游戏阶段
+    pub fn phase(&self) -> Phase {
+        if self.is_lottery {
+            Phase::Resolved
+        } else if self.is_playing {
+            Phase::Betting
+        } else {
+            Phase::Setup
+        }
+    }
+}
+
+/// 可以开奖的调用者
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LotteryCaller {
+    /// 任何人都可以开奖
+    #[default]
+    Anyone,
+    /// 只有本轮玩家和管理员可以开奖
+    PlayerOrOwner,
+    /// 只有管理员可以开奖
+    OwnerOnly,
+}
+
+/// 玩法
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GuessMode {
+    /// 猜单双
+    #[default]
+    Parity,
+    /// 猜具体的数字
+    Exact,
+}
+
+/// 游戏阶段
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// 准备中，管理员可以添加奖金
+    Setup,
+    /// 已开放投注，还没有开奖
+    Betting,
+    /// 已开奖
+    Resolved,
+}
+
+/// 托管中的奖金
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Claim {
+    /// 奖金金额
+    pub amount: Uint128,
+
+    /// 可以领取的时间
+    pub claimable_after: Timestamp,
+}
+
+/// 资金流水统计
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct FlowStats {
+    /// 用户累计下注金额
+    pub total_staked_ever: Uint128,
+
+    /// 管理员累计添加的奖金
+    pub total_bonus_ever: Uint128,
+
+    /// 开奖累计发放的金额
+    pub total_paid_ever: Uint128,
+
+    /// 庄家累计赢得的用户本金
+    pub total_house_income: Uint128,
+
+    /// 庄家累计付给用户的奖金，不含用户本金
+    pub total_house_payouts: Uint128,
+}
+
+/// 历史开奖数字的单双分布
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct DrawHistogram {
+    /// 开出单数的轮数
+    pub odd: u64,
+
+    /// 开出双数的轮数
+    pub even: u64,
+}
+
+/// 地址当天输掉的金额
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DailyLoss {
+    /// 从 UNIX 时间开始的天数
+    pub day: u64,
+
+    /// 当天累计输掉的金额
+    pub amount: Uint128,
+}
+
+/// 多人模式下玩家的下注
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Bet {
+    /// 押的是否是单
+    pub is_odd: bool,
+
+    /// 下注金额
+    pub stake: Uint128,
+}
+
+/// 每个地址的累计统计
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct UserStats {
+    /// 参与开奖的轮数
+    pub games_played: u64,
+
+    /// 猜对的轮数
+    #[serde(default)]
+    pub games_won: u64,
+
+    /// 累计下注金额
+    #[serde(default)]
+    pub total_wagered: Uint128,
+
+    /// 累计赢得的奖金，不含佣金
+    #[serde(default)]
+    pub total_won: Uint128,
+}
+
+/// 一轮的开奖记录
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoundRecord {
+    /// 本轮的数字
+    pub guess_number: i8,
+
+    /// 玩家押的是否是单
+    pub guess_is_odd: bool,
+
+    /// 本轮的玩家，多人模式下为空
+    pub user: Option<Addr>,
+
+    /// 本轮的奖金
+    pub bonus: Uint128,
+
+    /// 赢家，多人模式下为空
+    pub winner: Option<Addr>,
+
+    /// 开放投注时附带的备注信息
+    #[serde(default)]
+    pub metadata: Option<String>,
+}
+
+/// 0.1 版本的合约状态，只在升级合约时读取
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StateV0_1 {
+    pub guess_number: i8,
+    pub is_playing: bool,
+    pub is_lottery: bool,
+    pub guess_is_odd: bool,
+    pub user: Option<Addr>,
+    pub owner: Addr,
+    pub bonus: Uint128,
+    pub user_payed: Uint128,
 }
 
 pub const STATE: Item<State> = Item::new("state");
+pub const STATE_V0_1: Item<StateV0_1> = Item::new("state");
+pub const FLOW_STATS: Item<FlowStats> = Item::new("flow_stats");
+pub const DRAW_HISTOGRAM: Item<DrawHistogram> = Item::new("draw_histogram");
+pub const CLAIMS: Map<&Addr, Claim> = Map::new("claims");
+pub const DAILY_LOSSES: Map<&Addr, DailyLoss> = Map::new("daily_losses");
+pub const BETS: Map<&Addr, Bet> = Map::new("bets");
+pub const USER_STATS: Map<&Addr, UserStats> = Map::new("user_stats");
+pub const ROUNDS: Map<U64Key, RoundRecord> = Map::new("rounds");